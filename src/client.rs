@@ -4,9 +4,12 @@ use std::str;
 use crate::utils;
 use anyhow::Result;
 use async_std::prelude::*;
-use async_std::{io::ReadExt, os::unix::net::UnixStream};
+use async_std::{
+    io::{BufReader, ReadExt},
+    os::unix::net::UnixStream,
+};
 
-pub async fn send(socket_path: Option<String>, msg: &str) -> Result<()> {
+pub async fn send(socket_path: Option<String>, msg: &str) -> Result<String> {
     log::debug!("sending message: '{}'", msg);
     let socket_path = utils::get_socket_path(socket_path);
     let mut stream = UnixStream::connect(&socket_path).await?;
@@ -15,6 +18,20 @@ pub async fn send(socket_path: Option<String>, msg: &str) -> Result<()> {
     let mut response = String::new();
     stream.read_to_string(&mut response).await?;
     stream.shutdown(Shutdown::Read)?;
-    log::info!("-> {}", response);
+    Ok(response)
+}
+
+/// Sends the message and prints what the daemon responds with as it arrives,
+/// until the daemon closes the connection.
+pub async fn stream(socket_path: Option<String>, msg: &str) -> Result<()> {
+    log::debug!("sending message: '{}'", msg);
+    let socket_path = utils::get_socket_path(socket_path);
+    let mut stream = UnixStream::connect(&socket_path).await?;
+    stream.write_all(msg.as_bytes()).await?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut lines = BufReader::new(stream).lines();
+    while let Some(line) = lines.next().await {
+        println!("{}", line?);
+    }
     Ok(())
 }
@@ -1,9 +1,39 @@
-use crate::layout::{StackLayout, WorkspaceLayout, STACK_MAIN_DEFAULT_SIZE};
+use anyhow::{anyhow, Result};
+use clap::{parser::ValueSource, ArgMatches};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::criteria::{Criteria, Hook};
+use crate::layout::{
+    Orientation, PreselDirection, StackLayout, WorkspaceLayout, STACK_MAIN_DEFAULT_SIZE,
+};
+
+fn opacity_in_range(s: &str) -> Result<f32, String> {
+    let opacity: f32 = s
+        .parse()
+        .map_err(|_| format!("{s} is not a valid opacity"))?;
+    if (0.0..=1.0).contains(&opacity) {
+        return Ok(opacity);
+    }
+    Err(format!("opacity {s} is not between 0 and 1"))
+}
+
+/// Parses an `APP=VALUE` pair of the --app-name and --app-icon options, the app
+/// is matched against app_id or class ignoring case.
+fn app_mapping(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((app, value)) if !app.is_empty() => Ok((app.to_lowercase(), value.to_string())),
+        _ => Err(format!("{s} is not of the form APP=VALUE")),
+    }
+}
 
 #[derive(clap::Parser, Debug)]
 pub struct DaemonArgs {
     /// Which layout should be the default when no other layout has been specified for
-    /// a workspace. Options are: manual, spiral and stack_main.
+    /// a workspace. Options are: manual, spiral, dwindle, stack_main, monocle, columns,
+    /// rows, scrolling and bsp.
     #[arg(long, short = 'd', default_value = "manual")]
     pub default_layout: WorkspaceLayout,
 
@@ -21,6 +51,43 @@ pub struct DaemonArgs {
     #[arg(long, short = 'w')]
     pub workspace_renaming: bool,
 
+    /// The template workspaces are renamed after with --workspace-renaming. Can contain
+    /// {num}, the number of the workspace, {focused_app} and {focused_icon}, the name and
+    /// icon of the focused app, {apps} and {icons}, those of all apps the workspace is
+    /// named after, and {count}, the number of other windows on the workspace. Eg:
+    ///
+    /// --rename-format '{num} {icons}' or --rename-format '{num}: {focused_app} (+{count})'
+    #[arg(long, default_value = "{num}: {focused_app}")]
+    pub rename_format: String,
+
+    /// Which apps {apps} and {icons} name a workspace after: focused, only the focused
+    /// one, tree, all apps on the workspace including floating ones from left to right,
+    /// or mru, all apps most recently focused first. Apps with several windows are
+    /// shown once with their number of windows, eg. foot×2.
+    #[arg(long, default_value_t = RenameApps::Focused)]
+    pub rename_apps: RenameApps,
+
+    /// The name to show for an app in workspace names, matched against its app_id or
+    /// class. Can be given several times, eg:
+    ///
+    /// --app-name org.wezfurlong.wezterm=term
+    #[arg(long, value_name = "APP=NAME", value_parser = app_mapping)]
+    pub app_name: Vec<(String, String)>,
+
+    /// The icon to show for an app in workspace names with {icons} and {focused_icon},
+    /// eg. a Nerd Font glyph. Can be given several times like --app-name.
+    #[arg(long, value_name = "APP=ICON", value_parser = app_mapping)]
+    pub app_icon: Vec<(String, String)>,
+
+    /// The icon to show for apps without one given by --app-icon, their name is
+    /// shown otherwise.
+    #[arg(long)]
+    pub fallback_icon: Option<String>,
+
+    /// The longest workspace name to rename to, longer names are cut short with an ellipsis.
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub rename_max_length: Option<u16>,
+
     /// Called when window comes into focus. To automatically set the opacity of
     /// all other windows to 0.8 for example, you would set this to:
     ///
@@ -36,14 +103,119 @@ pub struct DaemonArgs {
     /// Called when window leaves focus. To automatically mark these for example, you would set
     /// this to:
     ///
-    /// mark --add _prev
+    /// mark --add _unfocused
     ///
-    /// and then in your sway config:
-    ///
-    /// bindsym Mod1+tab [con_mark=_prev] focus
+    /// To switch back to the previously focused window, bind focus-prev instead.
     #[arg(long, short = 'l')]
     pub on_window_focus_leave: Option<String>,
 
+    /// A sway command run on a window when it comes into focus, if the window matches the
+    /// criteria in front of the command. Criteria are written like sway's: app_id, class,
+    /// title and workspace take regular expressions, floating and tiling take no value.
+    /// Every part of the command applies to the focused window. Can be given several
+    /// times, eg. to dim terminals but not video players:
+    ///
+    /// --on-focus '[app_id="foot"] opacity 0.9' --on-focus '[app_id="mpv"] opacity 1'
+    #[arg(long)]
+    pub on_focus: Vec<Hook>,
+
+    /// Like --on-focus but run on a window when it leaves focus, eg:
+    ///
+    /// --on-focus-leave '[app_id="mpv"] inhibit_idle none'
+    #[arg(long)]
+    pub on_focus_leave: Vec<Hook>,
+
+    /// A sway command run on a window when it is opened, if the window matches the criteria
+    /// in front of the command, like --on-focus. Can be given several times, eg:
+    ///
+    /// --on-new '[app_id="pavucontrol"] floating enable'
+    #[arg(long)]
+    pub on_new: Vec<Hook>,
+
+    /// Like --on-new but run when a window is closed. As the window is gone, the command
+    /// isn't run on it and a workspace criteria never matches, the others do. Use
+    /// --exec-on-close to get its id. Eg:
+    ///
+    /// --on-close '[app_id="mpv"] output * dpms on'
+    #[arg(long)]
+    pub on_close: Vec<Hook>,
+
+    /// Like --on-new but run when a window enters or leaves fullscreen.
+    #[arg(long)]
+    pub on_fullscreen: Vec<Hook>,
+
+    /// Like --on-new but run when a window starts or stops floating, the floating and
+    /// tiling criteria tell which, eg:
+    ///
+    /// --on-floating '[floating] border normal'
+    #[arg(long)]
+    pub on_floating: Vec<Hook>,
+
+    /// Like --on-new but run when a window becomes urgent.
+    #[arg(long)]
+    pub on_urgent: Vec<Hook>,
+
+    /// Like --on-new but run when the title of a window changes.
+    #[arg(long)]
+    pub on_title: Vec<Hook>,
+
+    /// Like --on-new but run when the marks of a window change.
+    #[arg(long)]
+    pub on_mark: Vec<Hook>,
+
+    /// Criteria of windows to focus as soon as they become urgent, like those of --on-focus.
+    /// Can be given several times, eg:
+    ///
+    /// --auto-focus-urgent '[app_id="firefox"]'
+    #[arg(long)]
+    pub auto_focus_urgent: Vec<Criteria>,
+
+    /// A shell program run when a window comes into focus. Details about the window are
+    /// passed in the environment variables PERSWAY_EVENT, PERSWAY_WINDOW_ID, PERSWAY_APP_ID,
+    /// PERSWAY_APP_NAME, PERSWAY_TITLE, PERSWAY_WORKSPACE and PERSWAY_LAYOUT, and as JSON on
+    /// stdin. PERSWAY_APP_ID is the app_id as sway has it, PERSWAY_APP_NAME is lowercased and
+    /// falls back to the class or title. Can be given several times, eg. to switch the
+    /// keyboard layout per app:
+    ///
+    /// --exec-on-focus 'my-kbd-switcher "$PERSWAY_APP_ID"'
+    #[arg(long)]
+    pub exec_on_focus: Vec<String>,
+
+    /// Like --exec-on-focus but run when a window is opened.
+    #[arg(long)]
+    pub exec_on_new: Vec<String>,
+
+    /// Like --exec-on-focus but run when a window is closed. The workspace is left empty
+    /// as the window is gone.
+    #[arg(long)]
+    pub exec_on_close: Vec<String>,
+
+    /// Like --exec-on-focus but run when a window becomes urgent.
+    #[arg(long)]
+    pub exec_on_urgent: Vec<String>,
+
+    /// Dims windows that aren't focused to this opacity, between 0 and 1. Only the windows
+    /// gaining or losing focus are touched, fading over --dim-fade. Consider resetting the
+    /// opacity with --on-exit '[tiling] opacity 1'.
+    #[arg(long, value_parser = opacity_in_range)]
+    pub dim_inactive: Option<f32>,
+
+    /// The opacity of the focused window when dimming with --dim-inactive.
+    #[arg(long, value_parser = opacity_in_range, default_value_t = 1.0)]
+    pub dim_active: f32,
+
+    /// How many milliseconds dimmed windows take to fade in and out, 0 switches the
+    /// opacity at once.
+    #[arg(long, default_value_t = 150)]
+    pub dim_fade: u64,
+
+    /// Criteria of windows never to dim, like those of --on-focus. Can be given several
+    /// times, eg:
+    ///
+    /// --dim-exclude '[app_id="mpv"]' --dim-exclude '[floating]'
+    #[arg(long)]
+    pub dim_exclude: Vec<Criteria>,
+
     /// Called when persway exits. This can be used to reset any opacity changes
     /// or other settings when persway exits. For example, if changing the opacity
     /// on window focus, you would probably want to reset that on exit like this:
@@ -53,6 +225,66 @@ pub struct DaemonArgs {
     /// Eg. set all tiling windows to opacity 1
     #[arg(long, short = 'e')]
     pub on_exit: Option<String>,
+
+    /// Don't send any commands to sway, only log them together with the tree
+    /// they were computed from. Useful for debugging layouts.
+    #[arg(long, short = 'n')]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListFormat {
+    Dmenu,
+    Json,
+}
+
+impl FromStr for ListFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dmenu" => Ok(Self::Dmenu),
+            "json" => Ok(Self::Json),
+            s => Err(anyhow!("I don't know about the format '{}'", s)),
+        }
+    }
+}
+
+impl Display for ListFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dmenu => write!(f, "dmenu"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RenameApps {
+    Focused,
+    Tree,
+    Mru,
+}
+
+impl FromStr for RenameApps {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "focused" => Ok(Self::Focused),
+            "tree" => Ok(Self::Tree),
+            "mru" => Ok(Self::Mru),
+            s => Err(anyhow!("I don't know about renaming after '{}'", s)),
+        }
+    }
+}
+
+impl Display for RenameApps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Focused => write!(f, "focused"),
+            Self::Tree => write!(f, "tree"),
+            Self::Mru => write!(f, "mru"),
+        }
+    }
 }
 
 #[derive(clap::Parser, Debug)]
@@ -69,11 +301,181 @@ pub enum PerswayCommand {
     StackMainRotateNext,
     /// Applies to stack main layout - pops the top of the bottom of the stack into main while pushing the old main window to the top of the stack
     StackMainRotatePrev,
+    /// Applies to stack main layout - focuses the main window
+    StackFocusMain,
+    /// Applies to stack main layout - swaps the focused stacked window with the one above it
+    StackMoveUp,
+    /// Applies to stack main layout - swaps the focused stacked window with the one below it
+    StackMoveDown,
+    /// Applies to stack main layout - pushes the main window to the top of the stack, the next
+    /// window opened takes its place
+    StackPush,
+    /// Applies to stack main layout - focuses the stacked window at the given position,
+    /// counting from 1 at the top
+    StackFocusIndex {
+        #[arg(value_parser = clap::value_parser!(u16).range(1..))]
+        index: u16,
+    },
     /// Changes the layout of the focused workspace
     ChangeLayout {
         /// Change the layout of the focused workspace, can be any of:
-        /// manual, spiral, stack_main
+        /// manual, spiral, dwindle, stack_main, monocle, columns, rows, scrolling, bsp
         #[command(subcommand)]
         layout: WorkspaceLayout,
+        /// The layout options given on the command line, see note_given_options
+        #[arg(skip)]
+        given: Vec<String>,
+    },
+    /// Switches the focused workspace to the next layout of the given ones, options last
+    /// used for a layout on the workspace are kept
+    CycleLayout {
+        /// Comma separated layouts to cycle through, any of: manual, spiral, dwindle,
+        /// stack_main, monocle, columns, rows, scrolling, bsp
+        #[arg(
+            long,
+            short = 'l',
+            value_delimiter = ',',
+            default_value = "spiral,stack_main,manual"
+        )]
+        layouts: Vec<WorkspaceLayout>,
+    },
+    /// Switches the focused workspace to the first layout, or to the second one when it
+    /// already uses the first, options last used for a layout on the workspace are kept
+    ToggleLayout {
+        first: WorkspaceLayout,
+        second: WorkspaceLayout,
+    },
+    /// Switches the focused workspace to the monocle layout, or back to the layout it had
+    /// before, restoring the order of its windows
+    ToggleZoom,
+    /// Focuses the window focused before the current one, on any workspace. Repeating it
+    /// shortly after walks further back through the focus history, like alt-tab.
+    FocusPrev,
+    /// Walks the focus history forward again, see focus-prev
+    FocusNext,
+    /// Focuses the window that has been asking for attention the longest
+    FocusUrgent,
+    /// Prints the status of persway as JSON, currently the urgent windows
+    Status,
+    /// Prints the status of persway as JSON like status, and again on every change until
+    /// interrupted, eg. for a bar to show how many windows are urgent
+    Subscribe,
+    /// Restores the focused workspace to how it was arranged before the last command that
+    /// rearranged it, eg. change-layout or stack-swap-main
+    Undo,
+    /// Restores the arrangement of the focused workspace the last undo went back from
+    Redo,
+    /// Applies to scrolling layout - focuses the column to the left of the focused one,
+    /// scrolling it into view
+    ScrollLeft,
+    /// Applies to scrolling layout - focuses the column to the right of the focused one,
+    /// scrolling it into view
+    ScrollRight,
+    /// Applies to scrolling layout - sets the width of the columns of the focused workspace
+    ColumnWidth {
+        /// Width of a column in percent of the workspace
+        #[arg(value_parser = clap::value_parser!(u8).range(10..=100))]
+        width: u8,
+    },
+    /// Applies to bsp layout - chooses where the next window opened next to the focused one
+    /// goes: left, right, up or down of it. Cancel drops the preselection.
+    Presel { direction: PreselDirection },
+    /// Applies to bsp layout - rotates the windows of the focused workspace by 90 degrees
+    /// clockwise
+    Rotate {
+        /// Only rotate the container the focused window is in
+        #[arg(long, short = 'p')]
+        parent: bool,
+    },
+    /// Applies to bsp layout - mirrors the windows of the focused workspace, horizontal
+    /// swaps left and right, vertical swaps top and bottom
+    Flip {
+        orientation: Orientation,
+        /// Only flip the container the focused window is in
+        #[arg(long, short = 'p')]
+        parent: bool,
     },
+    /// Prints all windows with their workspace, app name, title and role in the layout:
+    /// main, stack or leaf. Meant for window switchers like fuzzel, wofi or rofi, see
+    /// focus-window.
+    ListWindows {
+        /// List the most recently focused windows first
+        #[arg(long, short = 'm')]
+        mru: bool,
+        /// How to print the windows: dmenu, one line per window starting with its id, or
+        /// json
+        #[arg(long, short = 'f', default_value_t = ListFormat::Dmenu)]
+        format: ListFormat,
+    },
+    /// Focuses the window with the given id, or the one a line printed by list-windows
+    /// starts with
+    FocusWindow {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        window: Vec<String>,
+    },
+    /// Prints the sway commands the given command would send, together with the tree of the
+    /// focused workspace they were computed from, without running them
+    Explain {
+        /// The command to explain, eg. stack-swap-main
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Records the window events sway sends to a file, together with the tree as it was
+    /// when each event arrived. Runs until interrupted.
+    Record {
+        /// The file to write the recording to
+        file: String,
+    },
+    /// Feeds a recording made with record through the layouts, computing commands from
+    /// the recorded trees. The commands are sent to sway unless --dry-run is given.
+    Replay {
+        /// The recording to replay
+        file: String,
+        /// Milliseconds to wait after each event so that layout commands are done
+        /// before the next event is replayed
+        #[arg(long, default_value_t = 100)]
+        delay: u64,
+        #[command(flatten)]
+        options: DaemonArgs,
+    },
+}
+
+impl PerswayCommand {
+    /// Whether the command changes the layout of the focused workspace or the order of its
+    /// windows, so that undo can go back to how it was before.
+    pub fn rearranges(&self) -> bool {
+        matches!(
+            self,
+            Self::StackSwapMain
+                | Self::StackMainRotateNext
+                | Self::StackMainRotatePrev
+                | Self::StackMoveUp
+                | Self::StackMoveDown
+                | Self::StackPush
+                | Self::ChangeLayout { .. }
+                | Self::CycleLayout { .. }
+                | Self::ToggleLayout { .. }
+                | Self::ToggleZoom
+                | Self::ColumnWidth { .. }
+                | Self::Rotate { .. }
+                | Self::Flip { .. }
+        )
+    }
+
+    /// Notes which layout options change-layout was given on the command line, the
+    /// others are restored from when the layout was last used on the workspace. Takes the
+    /// matches the command itself is a subcommand of.
+    pub fn note_given_options(&mut self, matches: &ArgMatches) {
+        if let Self::ChangeLayout { given, .. } = self {
+            if let Some((_, matches)) = matches.subcommand().and_then(|(_, m)| m.subcommand()) {
+                *given = matches
+                    .ids()
+                    .filter(|id| {
+                        matches.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+                    })
+                    .map(|id| id.to_string())
+                    .collect();
+            }
+        }
+    }
 }
@@ -0,0 +1,191 @@
+use crate::{node_ext::NodeExt, tree_cache::TreeCache};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::str::FromStr;
+use swayipc_async::Node;
+
+/// Which windows a hook applies to, written like sway's criteria, eg:
+///
+/// [app_id="^foot$" tiling workspace="^2"]
+///
+/// app_id, class, title and workspace take regular expressions, floating and
+/// tiling take no value. A window has to match all of them.
+#[derive(Clone, Debug, Default)]
+pub struct Criteria {
+    app_id: Option<Regex>,
+    class: Option<Regex>,
+    title: Option<Regex>,
+    workspace: Option<Regex>,
+    floating: Option<bool>,
+}
+
+impl Criteria {
+    pub fn matches(&self, tree: &TreeCache, node: &Node) -> bool {
+        let workspace = tree.workspace_of(node.id).map(|ws| ws.name.as_str());
+        self.matches_in(node, workspace)
+    }
+
+    /// Matches the window on its own, for windows sway has already removed from
+    /// the tree, a workspace criteria never matches.
+    pub fn matches_window(&self, node: &Node) -> bool {
+        self.matches_in(node, None)
+    }
+
+    fn matches_in(&self, node: &Node, workspace: Option<&str>) -> bool {
+        let matches = |regex: &Option<Regex>, value: Option<&str>| {
+            regex
+                .as_ref()
+                .is_none_or(|regex| value.is_some_and(|v| regex.is_match(v)))
+        };
+        let class = node
+            .window_properties
+            .as_ref()
+            .and_then(|p| p.class.as_deref());
+        matches(&self.app_id, node.app_id.as_deref())
+            && matches(&self.class, class)
+            && matches(&self.title, node.name.as_deref())
+            && matches(&self.workspace, workspace)
+            && self
+                .floating
+                .is_none_or(|floating| node.is_floating() == floating)
+    }
+}
+
+/// Splits the inside of the brackets into key and optional value pairs,
+/// values may be quoted.
+fn criteria_pairs(s: &str) -> Result<Vec<(String, Option<String>)>> {
+    let mut pairs = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(pairs);
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            pairs.push((key, None));
+            continue;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') if chars.peek() == Some(&'"') => value.extend(chars.next()),
+                    Some(c) => value.push(c),
+                    None => return Err(anyhow!("unterminated quote in criteria '{}'", s)),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        pairs.push((key, Some(value)));
+    }
+}
+
+/// The index of the bracket closing the criteria, skipping quoted values.
+fn criteria_end(s: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ']' if !quoted => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits a command list on the semicolons outside of quotes.
+fn split_commands(s: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                commands.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    commands.push(&s[start..]);
+    commands
+}
+
+impl FromStr for Criteria {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let inner = s
+            .trim()
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| anyhow!("criteria '{}' should be within brackets", s))?;
+        let mut criteria = Self::default();
+        for (key, value) in criteria_pairs(inner)? {
+            match (key.as_str(), value) {
+                ("floating", None) => criteria.floating = Some(true),
+                ("tiling", None) => criteria.floating = Some(false),
+                ("app_id", Some(value)) => criteria.app_id = Some(Regex::new(&value)?),
+                ("class", Some(value)) => criteria.class = Some(Regex::new(&value)?),
+                ("title", Some(value)) => criteria.title = Some(Regex::new(&value)?),
+                ("workspace", Some(value)) => criteria.workspace = Some(Regex::new(&value)?),
+                (key, _) => return Err(anyhow!("I don't know about the criteria '{}'", key)),
+            }
+        }
+        Ok(criteria)
+    }
+}
+
+/// A sway command run on the windows matching the criteria in front of it,
+/// see Criteria. Without criteria it applies to every window.
+#[derive(Clone, Debug)]
+pub struct Hook {
+    pub criteria: Criteria,
+    pub command: String,
+}
+
+impl Hook {
+    /// The command with every part of it targeting the given window.
+    pub fn command_for(&self, id: i64) -> String {
+        split_commands(&self.command)
+            .into_iter()
+            .map(|cmd| format!("[con_id={}] {}", id, cmd.trim()))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+impl FromStr for Hook {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (criteria, command) = if s.starts_with('[') {
+            let end = criteria_end(s)
+                .ok_or_else(|| anyhow!("criteria of hook '{}' should end with ]", s))?;
+            (s[..=end].parse()?, s[end + 1..].trim())
+        } else {
+            (Criteria::default(), s)
+        };
+        if command.is_empty() {
+            return Err(anyhow!("hook '{}' has no command", s));
+        }
+        Ok(Self {
+            criteria,
+            command: command.to_string(),
+        })
+    }
+}
@@ -6,17 +6,36 @@ use std::{
 };
 
 pub const STACK_MAIN_DEFAULT_SIZE: u8 = 70;
+/// The share of a split the older window keeps in the dwindle layout, close
+/// to the golden ratio.
+pub const DWINDLE_DEFAULT_RATIO: u8 = 62;
+pub const SCROLLING_DEFAULT_WIDTH: u8 = 50;
 
 impl FromStr for WorkspaceLayout {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self> {
         match s {
-            "spiral" => Ok(Self::Spiral),
+            "spiral" => Ok(Self::Spiral {
+                ratio: None,
+                max_depth: None,
+                overflow_layout: StackLayout::Tabbed,
+            }),
+            "dwindle" => Ok(Self::Dwindle {
+                ratio: DWINDLE_DEFAULT_RATIO,
+                direction: None,
+            }),
             "stack_main" => Ok(Self::StackMain {
                 stack_layout: StackLayout::Stacked,
                 size: STACK_MAIN_DEFAULT_SIZE,
             }),
             "manual" => Ok(Self::Manual),
+            "monocle" => Ok(Self::Monocle),
+            "columns" => Ok(Self::Columns { max: None }),
+            "rows" => Ok(Self::Rows { max: None }),
+            "bsp" => Ok(Self::Bsp),
+            "scrolling" => Ok(Self::Scrolling {
+                width: SCROLLING_DEFAULT_WIDTH,
+            }),
             s => Err(anyhow!("I don't know about the layout '{}'", s)),
         }
     }
@@ -25,16 +44,135 @@ impl FromStr for WorkspaceLayout {
 impl Display for WorkspaceLayout {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let string_layout = match self {
-            Self::Spiral => String::from("spiral"),
+            Self::Spiral {
+                ratio,
+                max_depth,
+                overflow_layout,
+            } => {
+                let mut layout = String::from("spiral");
+                if let Some(ratio) = ratio {
+                    layout.push_str(&format!(" ratio {}", ratio));
+                }
+                if let Some(max_depth) = max_depth {
+                    layout.push_str(&format!(" max_depth {} {}", max_depth, overflow_layout));
+                }
+                layout
+            }
+            Self::Dwindle {
+                ratio,
+                direction: None,
+            } => format!("dwindle {}", ratio),
+            Self::Dwindle {
+                ratio,
+                direction: Some(direction),
+            } => format!("dwindle {} {}", ratio, direction),
             Self::StackMain { stack_layout, size } => {
                 format!("stack_main {} {}", stack_layout, size)
             }
             Self::Manual => String::from("manual"),
+            Self::Monocle => String::from("monocle"),
+            Self::Columns { max: None } => String::from("columns"),
+            Self::Columns { max: Some(max) } => format!("columns {}", max),
+            Self::Rows { max: None } => String::from("rows"),
+            Self::Rows { max: Some(max) } => format!("rows {}", max),
+            Self::Scrolling { width } => format!("scrolling {}", width),
+            Self::Bsp => String::from("bsp"),
         };
         write!(f, "{}", string_layout)
     }
 }
 
+impl WorkspaceLayout {
+    /// The name of the layout without its options, as accepted by FromStr.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Spiral { .. } => "spiral",
+            Self::Dwindle { .. } => "dwindle",
+            Self::StackMain { .. } => "stack_main",
+            Self::Manual => "manual",
+            Self::Monocle => "monocle",
+            Self::Columns { .. } => "columns",
+            Self::Rows { .. } => "rows",
+            Self::Bsp => "bsp",
+            Self::Scrolling { .. } => "scrolling",
+        }
+    }
+
+    /// This layout with the given options taken from another one of the same kind, or the
+    /// other layout when they differ.
+    pub fn with_options(mut self, other: WorkspaceLayout, given: &[String]) -> Self {
+        let given = |option: &str| given.iter().any(|g| g == option);
+        match (&mut self, other) {
+            (
+                Self::Spiral {
+                    ratio,
+                    max_depth,
+                    overflow_layout,
+                },
+                Self::Spiral {
+                    ratio: other_ratio,
+                    max_depth: other_max_depth,
+                    overflow_layout: other_overflow_layout,
+                },
+            ) => {
+                if given("ratio") {
+                    *ratio = other_ratio;
+                }
+                if given("max_depth") {
+                    *max_depth = other_max_depth;
+                }
+                if given("overflow_layout") {
+                    *overflow_layout = other_overflow_layout;
+                }
+            }
+            (
+                Self::Dwindle { ratio, direction },
+                Self::Dwindle {
+                    ratio: other_ratio,
+                    direction: other_direction,
+                },
+            ) => {
+                if given("ratio") {
+                    *ratio = other_ratio;
+                }
+                if given("direction") {
+                    *direction = other_direction;
+                }
+            }
+            (
+                Self::StackMain { size, stack_layout },
+                Self::StackMain {
+                    size: other_size,
+                    stack_layout: other_stack_layout,
+                },
+            ) => {
+                if given("size") {
+                    *size = other_size;
+                }
+                if given("stack_layout") {
+                    *stack_layout = other_stack_layout;
+                }
+            }
+            (Self::Columns { max }, Self::Columns { max: other_max })
+            | (Self::Rows { max }, Self::Rows { max: other_max }) => {
+                if given("max") {
+                    *max = other_max;
+                }
+            }
+            (Self::Scrolling { width }, Self::Scrolling { width: other_width }) => {
+                if given("width") {
+                    *width = other_width;
+                }
+            }
+            (Self::Manual, Self::Manual)
+            | (Self::Monocle, Self::Monocle)
+            | (Self::Bsp, Self::Bsp) => {}
+            (_, other) => return other,
+        }
+        self
+    }
+}
+
 const SIZE_RANGE: RangeInclusive<usize> = 10..=90;
 
 fn size_in_range(s: &str) -> Result<u8, String> {
@@ -49,6 +187,18 @@ fn size_in_range(s: &str) -> Result<u8, String> {
     ))
 }
 
+/// Only tabbed and stacked containers keep windows beyond spiral's max depth
+/// from splitting further, a tiled one would just split again.
+fn overflow_layout(s: &str) -> Result<StackLayout, String> {
+    match s.parse() {
+        Ok(StackLayout::Tiled) => Err(String::from(
+            "windows beyond the max depth share a tabbed or stacked container, not a tiled one",
+        )),
+        Ok(layout) => Ok(layout),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 impl FromStr for StackLayout {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self> {
@@ -72,6 +222,77 @@ impl Display for StackLayout {
     }
 }
 
+impl FromStr for SplitDirection {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            "alternate" => Ok(Self::Alternate),
+            s => Err(anyhow!("I don't know about the split direction '{}'", s)),
+        }
+    }
+}
+
+impl Display for SplitDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let string_direction = match self {
+            Self::Horizontal => "horizontal",
+            Self::Vertical => "vertical",
+            Self::Alternate => "alternate",
+        };
+        write!(f, "{}", string_direction)
+    }
+}
+
+impl FromStr for PreselDirection {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            "cancel" => Ok(Self::Cancel),
+            s => Err(anyhow!("I don't know about the preselection '{}'", s)),
+        }
+    }
+}
+
+impl Display for PreselDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let string_direction = match self {
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Cancel => "cancel",
+        };
+        write!(f, "{}", string_direction)
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            s => Err(anyhow!("I don't know about the orientation '{}'", s)),
+        }
+    }
+}
+
+impl Display for Orientation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let string_orientation = match self {
+            Self::Horizontal => "horizontal",
+            Self::Vertical => "vertical",
+        };
+        write!(f, "{}", string_orientation)
+    }
+}
+
 #[derive(clap::Parser, Debug, Clone, PartialEq)]
 pub enum StackLayout {
     Tabbed,
@@ -79,10 +300,60 @@ pub enum StackLayout {
     Tiled,
 }
 
+/// How the dwindle layout splits windows when not following their geometry.
+#[derive(clap::Parser, Debug, Clone, PartialEq)]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+    /// Alternates between horizontal and vertical splits with each level of nesting
+    Alternate,
+}
+
+/// Where the bsp layout puts the next window opened next to the focused one.
+#[derive(clap::Parser, Debug, Clone, PartialEq)]
+pub enum PreselDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// Drops the preselection
+    Cancel,
+}
+
+#[derive(clap::Parser, Debug, Clone, PartialEq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
 #[derive(clap::Parser, Debug, Clone, PartialEq)]
 pub enum WorkspaceLayout {
     /// The spiral autotiling layout tiles windows in a spiral formation, similar to AwesomeWM
-    Spiral,
+    Spiral {
+        /// Share of a split in percent kept by the window being split, splits are even
+        /// when not given
+        #[arg(long, short = 'r', value_parser = size_in_range)]
+        ratio: Option<u8>,
+        /// How deeply windows may be nested. Windows beyond that depth share a container
+        /// at the deepest level instead of splitting further.
+        #[arg(long, short = 'm')]
+        max_depth: Option<u8>,
+        /// The sway layout of the container windows beyond max-depth share: tabbed or
+        /// stacked.
+        #[arg(long, short = 'o', value_parser = overflow_layout, default_value_t = StackLayout::Tabbed)]
+        overflow_layout: StackLayout,
+    },
+    /// The dwindle layout splits windows like spiral but gives the window being split the
+    /// larger share, by default close to the golden ratio
+    Dwindle {
+        /// Share of a split in percent kept by the window being split
+        #[arg(long, short = 'r', value_parser = size_in_range, default_value_t = DWINDLE_DEFAULT_RATIO)]
+        ratio: u8,
+        /// Always split in this direction instead of along the longer side of the window:
+        /// horizontal, vertical or alternate
+        #[arg(long, short = 'd')]
+        direction: Option<SplitDirection>,
+    },
     /// The stack_main autotiling layout keeps a stack of windows on the side of a larger main area, this layout comes with a few commands to control it as well
     StackMain {
         /// Size of the main area in percent
@@ -94,4 +365,29 @@ pub enum WorkspaceLayout {
     },
     /// The standard sway manual tiling
     Manual,
+    /// The monocle layout keeps all windows of the workspace in a single tabbed container,
+    /// see toggle-zoom
+    Monocle,
+    /// The columns layout keeps windows side by side with equal widths
+    Columns {
+        /// The number of columns in a row, further windows wrap into a new row
+        #[arg(long, short = 'm', value_parser = clap::value_parser!(u8).range(1..))]
+        max: Option<u8>,
+    },
+    /// The rows layout keeps windows on top of each other with equal heights
+    Rows {
+        /// The number of rows in a column, further windows wrap into a new column
+        #[arg(long, short = 'm', value_parser = clap::value_parser!(u8).range(1..))]
+        max: Option<u8>,
+    },
+    /// The bsp layout splits the focused window along its longer side like spiral, see presel
+    /// for choosing the direction and side of the next split instead
+    Bsp,
+    /// The scrolling layout opens each window as a column to the right of the focused one
+    /// and scrolls the columns that don't fit out of view, similar to PaperWM and niri
+    Scrolling {
+        /// Width of a column in percent of the workspace
+        #[arg(long, short = 'w', value_parser = clap::value_parser!(u8).range(10..=100), default_value_t = SCROLLING_DEFAULT_WIDTH)]
+        width: u8,
+    },
 }
@@ -2,9 +2,11 @@ use anyhow::Result;
 use env_logger::Env;
 mod client;
 mod commands;
+mod criteria;
 mod layout;
 mod node_ext;
 mod server;
+mod tree_cache;
 use clap::Parser;
 mod utils;
 
@@ -33,13 +35,32 @@ async fn main() -> Result<()> {
                 .run()
                 .await?
         }
+        commands::PerswayCommand::Record { file } => server::recording::record(&file).await?,
+        commands::PerswayCommand::Replay {
+            file,
+            delay,
+            options,
+        } => server::recording::replay(&file, delay, options).await?,
+        commands::PerswayCommand::Subscribe => {
+            client::stream(
+                args.socket_path,
+                &std::env::args().collect::<Vec<_>>().join(" "),
+            )
+            .await?
+        }
         _ => {
             log::debug!("command: {:?}", args.command);
-            client::send(
+            let response = client::send(
                 args.socket_path,
                 &std::env::args().collect::<Vec<_>>().join(" "),
             )
-            .await?
+            .await?;
+            match args.command {
+                commands::PerswayCommand::Explain { .. }
+                | commands::PerswayCommand::ListWindows { .. }
+                | commands::PerswayCommand::Status => print!("{}", response),
+                _ => log::info!("-> {}", response),
+            }
         }
     }
     Ok(())
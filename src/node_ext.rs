@@ -1,5 +1,6 @@
+use crate::tree_cache::TreeCache;
 use anyhow::{anyhow, Result};
-use swayipc_async::{Connection, Node, NodeLayout, NodeType, Workspace};
+use swayipc_async::{Node, NodeLayout, NodeType, Workspace};
 
 pub enum RefinedNodeType {
     Root,
@@ -9,42 +10,13 @@ pub enum RefinedNodeType {
     FloatingContainer, // doesn't directly contain an application
     FloatingWindow,    // directly contains an application
     Window,            // directly contains an application
-}
-
-#[derive(Clone)]
-pub struct LinearNodeIterator<'a> {
-    stack: Vec<&'a Node>,
-}
-
-impl<'a> LinearNodeIterator<'a> {
-    fn new(root: &'a Node) -> LinearNodeIterator<'a> {
-        let mut stack = Vec::with_capacity(100);
-        stack.push(root);
-        LinearNodeIterator { stack }
-    }
-}
-
-impl<'a> Iterator for LinearNodeIterator<'a> {
-    type Item = &'a Node;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let node = self.stack.pop()?;
-        for entry in &node.floating_nodes {
-            self.stack.push(entry);
-        }
-        for entry in &node.nodes {
-            self.stack.push(entry);
-        }
-        Some(node)
-    }
+    Unknown,           // couldn't be classified, eg. a Con without a pid during xwayland startup
 }
 
 pub trait NodeExt {
-    async fn get_workspace(&self) -> Result<Workspace>;
+    fn get_workspace(&self, tree: &TreeCache) -> Result<Workspace>;
     fn get_refined_node_type(&self) -> RefinedNodeType;
-    async fn get_parent(&self) -> Result<Node>;
-    fn iter(&self) -> LinearNodeIterator;
-    fn is_root(&self) -> bool;
+    fn get_parent<'a>(&self, tree: &'a TreeCache) -> Result<&'a Node>;
     fn is_output(&self) -> bool;
     fn is_workspace(&self) -> bool;
     fn is_container(&self) -> bool;
@@ -52,46 +24,24 @@ pub trait NodeExt {
     fn is_floating(&self) -> bool;
     fn is_window(&self) -> bool;
     fn is_floating_window(&self) -> bool;
+    fn is_any_window(&self) -> bool;
     fn is_full_screen(&self) -> bool;
-    async fn is_stacked(&self) -> Result<bool>;
-    async fn is_tabbed(&self) -> Result<bool>;
+    fn is_stacked(&self, tree: &TreeCache) -> Result<bool>;
+    fn is_tabbed(&self, tree: &TreeCache) -> Result<bool>;
 }
 
 impl NodeExt for Node {
-    fn iter(&self) -> LinearNodeIterator {
-        LinearNodeIterator::new(self)
-    }
-
-    async fn get_workspace(&self) -> Result<Workspace> {
-        let mut connection = Connection::new().await?;
-        let tree = connection.get_tree().await?;
-        let workspaces = connection.get_workspaces().await?;
-        let wsnode = tree
-            .find(|n| n.is_workspace() && n.iter().any(|n| n.id == self.id))
-            .ok_or(anyhow!(format!(
-                "no workspace found for node with id {}",
-                self.id
-            )))?;
-        workspaces
-            .iter()
-            .find(|w| w.id == wsnode.id)
-            .ok_or(anyhow!(format!(
-                "hmm no workspace found with id {}",
-                wsnode.id
-            )))
+    fn get_workspace(&self, tree: &TreeCache) -> Result<Workspace> {
+        tree.workspace_of(self.id)
             .cloned()
+            .ok_or_else(|| anyhow!(format!("no workspace found for node with id {}", self.id)))
     }
 
-    async fn get_parent(&self) -> Result<Node> {
-        let mut connection = Connection::new().await?;
-        let tree = connection.get_tree().await?;
-        tree.find(|n| n.nodes.iter().any(|n| n.id == self.id))
+    fn get_parent<'a>(&self, tree: &'a TreeCache) -> Result<&'a Node> {
+        tree.parent_of(self.id)
             .ok_or_else(|| anyhow!(format!("couldn't find parent of node id: {}", self.id)))
     }
 
-    fn is_root(&self) -> bool {
-        matches!(self.get_refined_node_type(), RefinedNodeType::Root)
-    }
     fn is_output(&self) -> bool {
         matches!(self.get_refined_node_type(), RefinedNodeType::Output)
     }
@@ -117,6 +67,10 @@ impl NodeExt for Node {
         )
     }
 
+    fn is_any_window(&self) -> bool {
+        self.is_window() || self.is_floating_window()
+    }
+
     fn is_floating(&self) -> bool {
         self.is_floating_container() || self.is_floating_window()
     }
@@ -125,13 +79,13 @@ impl NodeExt for Node {
         self.percent.unwrap_or(1.0) > 1.0
     }
 
-    async fn is_stacked(&self) -> Result<bool> {
-        let parent = self.get_parent().await?;
+    fn is_stacked(&self, tree: &TreeCache) -> Result<bool> {
+        let parent = self.get_parent(tree)?;
         Ok(parent.layout == NodeLayout::Stacked)
     }
 
-    async fn is_tabbed(&self) -> Result<bool> {
-        let parent = self.get_parent().await?;
+    fn is_tabbed(&self, tree: &TreeCache) -> Result<bool> {
+        let parent = self.get_parent(tree)?;
         Ok(parent.layout == NodeLayout::Tabbed)
     }
 
@@ -164,10 +118,13 @@ impl NodeExt for Node {
                 } else if self.node_type == NodeType::FloatingCon && self.pid.is_some() {
                     RefinedNodeType::FloatingWindow
                 } else {
-                    panic!(
-                            "Boom, don't know what type of node this is:\nid: {}\nnode_type: {:?}\n{:?}",
-                            self.id, self.node_type, self
-                        )
+                    log::debug!(
+                        "don't know what type of node this is:\nid: {}\nnode_type: {:?}\n{:?}",
+                        self.id,
+                        self.node_type,
+                        self
+                    );
+                    RefinedNodeType::Unknown
                 }
             }
         }
@@ -1,4 +1,8 @@
 mod command_handlers;
+pub mod command_runner;
 pub mod daemon;
 mod event_handlers;
+mod focus_history;
 mod message_handler;
+pub mod recording;
+mod urgency;
@@ -1 +1,3 @@
+pub mod bsp;
+pub mod scrolling;
 pub mod stack_main;
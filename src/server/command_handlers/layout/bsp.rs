@@ -0,0 +1,132 @@
+use crate::{
+    layout::{Orientation, PreselDirection},
+    node_ext::NodeExt,
+    server::{
+        command_runner::{CommandRunner, RunMode},
+        event_handlers::layout::bsp::{longer_side, Presel},
+    },
+    tree_cache::TreeCache,
+};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use swayipc_async::{Node, NodeLayout};
+
+/// Swap commands reversing the order of the nodes.
+fn reverse(nodes: &[Node]) -> String {
+    let mut cmd = String::new();
+    for i in 0..nodes.len() / 2 {
+        cmd.push_str(&format!(
+            "[con_id={}] swap container with con_id {}; ",
+            nodes[i].id,
+            nodes[nodes.len() - 1 - i].id
+        ));
+    }
+    cmd
+}
+
+pub struct Bsp {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+}
+
+impl Bsp {
+    pub async fn new(tree: Arc<TreeCache>, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::new(mode).await?;
+        Ok(Self { commands, tree })
+    }
+
+    fn focused_window(&self) -> Result<&Node> {
+        let ws = self
+            .tree
+            .focused_workspace()
+            .context("no focused workspace")?;
+        let wstree = self
+            .tree
+            .get(ws.id)
+            .context("workspace not found in tree")?;
+        wstree
+            .find_as_ref(|n| n.is_window() && n.focused)
+            .context("no focused window")
+    }
+
+    /// The focused workspace, or the container of the focused window.
+    fn subtree(&self, parent: bool) -> Result<&Node> {
+        if parent {
+            let window = self.focused_window()?;
+            return self
+                .tree
+                .parent_of(window.id)
+                .context("focused window has no parent");
+        }
+        let ws = self
+            .tree
+            .focused_workspace()
+            .context("no focused workspace")?;
+        self.tree.get(ws.id).context("workspace not found in tree")
+    }
+
+    async fn run(&mut self, cmd: String) -> Result<()> {
+        if !cmd.is_empty() {
+            log::debug!("bsp controller: {}", cmd);
+            self.commands
+                .run_command(cmd.trim_end_matches("; "))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Splits the focused window the way the preselection asks for, returning
+    /// the preselection for the layout to place the next window by.
+    pub async fn presel(&mut self, direction: PreselDirection) -> Result<Option<Presel>> {
+        let window = self.focused_window()?;
+        let presel = match direction {
+            PreselDirection::Cancel => None,
+            direction => Some(Presel {
+                window: window.id,
+                direction,
+            }),
+        };
+        let split = match &presel {
+            Some(presel) => presel.split(),
+            None => longer_side(window),
+        };
+        let cmd = format!("[con_id={}] split {}", window.id, split);
+        self.run(cmd).await?;
+        Ok(presel)
+    }
+
+    /// Rotates clockwise: left to right becomes top to bottom and top to
+    /// bottom becomes right to left.
+    pub async fn rotate(&mut self, parent: bool) -> Result<()> {
+        let subtree = self.subtree(parent)?;
+        let mut cmd = String::new();
+        for node in subtree.iter().filter(|n| !n.nodes.is_empty()) {
+            match node.layout {
+                NodeLayout::SplitH => {
+                    cmd.push_str(&format!("[con_id={}] layout splitv; ", node.nodes[0].id));
+                }
+                NodeLayout::SplitV => {
+                    cmd.push_str(&format!("[con_id={}] layout splith; ", node.nodes[0].id));
+                    cmd.push_str(&reverse(&node.nodes));
+                }
+                _ => {}
+            }
+        }
+        self.run(cmd).await
+    }
+
+    /// Mirrors horizontally (left and right) or vertically (top and bottom).
+    pub async fn flip(&mut self, orientation: Orientation, parent: bool) -> Result<()> {
+        let layout = match orientation {
+            Orientation::Horizontal => NodeLayout::SplitH,
+            Orientation::Vertical => NodeLayout::SplitV,
+        };
+        let subtree = self.subtree(parent)?;
+        let cmd: String = subtree
+            .iter()
+            .filter(|n| n.layout == layout)
+            .map(|n| reverse(&n.nodes))
+            .collect();
+        self.run(cmd).await
+    }
+}
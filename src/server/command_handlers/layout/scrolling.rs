@@ -0,0 +1,72 @@
+use crate::{
+    server::{
+        command_runner::{CommandRunner, RunMode},
+        event_handlers::layout::scrolling::{focused_leaf, Scrolling as ScrollingLayout, Strip},
+    },
+    tree_cache::TreeCache,
+};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+
+pub struct Scrolling {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    mode: RunMode,
+}
+
+impl Scrolling {
+    pub async fn new(tree: Arc<TreeCache>, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::new(mode.clone()).await?;
+        Ok(Self {
+            commands,
+            tree,
+            mode,
+        })
+    }
+
+    /// Focuses the column next to the focused one, the scrolling layout
+    /// scrolls it into view when the focus event arrives.
+    async fn scroll(&mut self, reverse: bool) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+        let strip = Strip::of(wstree);
+        let focused = match strip.focused {
+            Some(focused) => focused,
+            None => return Ok(()),
+        };
+        let target = if reverse {
+            focused.checked_sub(1)
+        } else {
+            Some(focused + 1)
+        };
+        if let Some(column) = target
+            .and_then(|i| strip.columns().nth(i))
+            .and_then(|id| tree.get(id))
+        {
+            let cmd = format!("[con_id={}] focus", focused_leaf(&tree, column));
+            log::debug!("scrolling controller, scroll: {}", cmd);
+            self.commands.run_command(cmd).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn scroll_left(&mut self) -> Result<()> {
+        self.scroll(true).await
+    }
+
+    pub async fn scroll_right(&mut self) -> Result<()> {
+        self.scroll(false).await
+    }
+
+    /// Rearranges the focused workspace for columns of the given width.
+    pub async fn column_width(&mut self, width: u8) -> Result<()> {
+        let ws = self
+            .tree
+            .focused_workspace()
+            .context("no focused workspace")?;
+        let mut layout =
+            ScrollingLayout::new(Arc::clone(&self.tree), width, self.mode.clone()).await?;
+        layout.arrange(ws.id).await
+    }
+}
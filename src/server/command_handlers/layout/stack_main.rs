@@ -1,22 +1,27 @@
-use crate::{node_ext::NodeExt, utils::get_focused_workspace};
-use anyhow::Result;
+use crate::{
+    node_ext::NodeExt,
+    server::command_runner::{CommandRunner, RunMode},
+    tree_cache::TreeCache,
+};
+use anyhow::{Context, Result};
 use either::Either;
-use swayipc_async::Connection;
+use std::sync::Arc;
 
 pub struct StackMain {
-    connection: Connection,
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
 }
 
 impl StackMain {
-    pub async fn new() -> Result<Self> {
-        let connection = Connection::new().await?;
-        Ok(Self { connection })
+    pub async fn new(tree: Arc<TreeCache>, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::new(mode).await?;
+        Ok(Self { commands, tree })
     }
 
     async fn stack_focus_advance(&mut self, reverse: bool) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
-        let ws = get_focused_workspace(&mut self.connection).await?;
-        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
 
         if let Some(stack) = wstree.nodes.first() {
             if stack.nodes.is_empty() {
@@ -36,9 +41,11 @@ impl StackMain {
             let stack_current = if let Some(focused) = focused {
                 focused
             } else if visible.count() == 1 {
-                stack.find_as_ref(|n| n.visible.unwrap_or(false)).unwrap()
+                stack
+                    .find_as_ref(|n| n.visible.unwrap_or(false))
+                    .context("visible stacked window not found")?
             } else {
-                initial.unwrap()
+                initial.context("stack is empty")?
             };
 
             let mut prev_was_focused = false;
@@ -51,7 +58,7 @@ impl StackMain {
                 if prev_was_focused {
                     let cmd = format!("[con_id={}] focus;", node.id);
                     log::debug!("stack main controller, stack focus prev: {}", cmd);
-                    self.connection.run_command(cmd).await?;
+                    self.commands.run_command(cmd).await?;
                     return Ok(());
                 }
                 prev_was_focused = node.id == stack_current.id
@@ -69,16 +76,16 @@ impl StackMain {
     }
 
     pub async fn stack_main_rotate(&mut self, reverse: bool) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
-        let ws = get_focused_workspace(&mut self.connection).await?;
-        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
 
         if let Some(stack) = wstree.nodes.first() {
             if stack.nodes.is_empty() {
                 return Ok(());
             }
 
-            let main = wstree.nodes.last().expect("main window not found");
+            let main = wstree.nodes.last().context("main window not found")?;
             let stack_leaves = stack.iter().filter(|n| n.is_window());
 
             let mut cmd = String::from("");
@@ -96,7 +103,7 @@ impl StackMain {
                 }
                 cmd.push_str(&format!(
                     "[con_id={}] focus; [con_id={}] focus; ",
-                    stack.nodes.first().unwrap().id,
+                    stack.nodes.first().context("stack is empty")?.id,
                     main.id
                 ));
             } else {
@@ -111,18 +118,18 @@ impl StackMain {
                 }
                 cmd.push_str(&format!(
                     "[con_id={}] focus; [con_id={}] focus; ",
-                    stack.nodes.last().unwrap().id,
+                    stack.nodes.last().context("stack is empty")?.id,
                     main.id
                 ));
             }
 
             log::debug!("stack main controller, master cycle next 1: {}", cmd);
-            self.connection.run_command(cmd).await?;
+            self.commands.run_command(cmd).await?;
 
-            let tree = self.connection.get_tree().await?;
-            let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
-            let main = wstree.nodes.last().expect("main window not found");
-            let stack = wstree.nodes.first().expect("stack container not found");
+            let tree = TreeCache::fetch(self.commands.connection()).await?;
+            let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+            let main = wstree.nodes.last().context("main window not found")?;
+            let stack = wstree.nodes.first().context("stack container not found")?;
 
             let cmd = if reverse {
                 let stack_last = stack
@@ -132,7 +139,7 @@ impl StackMain {
                     .collect::<Vec<_>>()
                     .into_iter()
                     .last()
-                    .unwrap();
+                    .context("no window in the stack")?;
 
                 format!(
                     "[con_id={}] focus; swap container with con_id {}; [con_id={}] focus",
@@ -146,7 +153,7 @@ impl StackMain {
                     .collect::<Vec<_>>()
                     .into_iter()
                     .next()
-                    .unwrap();
+                    .context("no window in the stack")?;
 
                 format!(
                     "[con_id={}] focus; swap container with con_id {}; [con_id={}] focus",
@@ -154,7 +161,7 @@ impl StackMain {
                 )
             };
             log::debug!("stack main controller, master cycle next 2: {}", cmd);
-            self.connection.run_command(cmd).await?;
+            self.commands.run_command(cmd).await?;
             return Ok(());
         }
         Ok(())
@@ -169,16 +176,16 @@ impl StackMain {
     }
 
     pub async fn stack_swap_main(&mut self) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
-        let ws = get_focused_workspace(&mut self.connection).await?;
-        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
 
         if let Some(stack) = wstree.nodes.first() {
             if stack.nodes.is_empty() {
                 return Ok(());
             }
 
-            let main = wstree.nodes.last().expect("main window not found");
+            let main = wstree.nodes.last().context("main window not found")?;
 
             let focused = stack.find_as_ref(|n| n.is_window() && n.focused);
             let visible = stack
@@ -189,9 +196,11 @@ impl StackMain {
             let stack_current = if let Some(focused) = focused {
                 focused
             } else if visible.count() == 1 {
-                stack.find_as_ref(|n| n.visible.unwrap_or(false)).unwrap()
+                stack
+                    .find_as_ref(|n| n.visible.unwrap_or(false))
+                    .context("visible stacked window not found")?
             } else {
-                initial.unwrap()
+                initial.context("stack is empty")?
             };
 
             let cmd = format!(
@@ -199,7 +208,105 @@ impl StackMain {
                 main.id, stack_current.id, stack_current.id
             );
             log::debug!("stack main controller, swap visible: {}", cmd);
-            self.connection.run_command(cmd).await?;
+            self.commands.run_command(cmd).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn stack_focus_main(&mut self) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+
+        if let Some(main) = wstree.nodes.last().filter(|n| n.is_window()) {
+            let cmd = format!("[con_id={}] focus", main.id);
+            log::debug!("stack main controller, focus main: {}", cmd);
+            self.commands.run_command(cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Swaps the focused stacked window with the one above or below it.
+    pub async fn stack_move(&mut self, up: bool) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+
+        if let Some(stack) = wstree.nodes.first().filter(|n| !n.is_window()) {
+            let focused = match stack.nodes.iter().position(|n| n.focused) {
+                Some(focused) => focused,
+                None => return Ok(()),
+            };
+            let other = if up {
+                focused.checked_sub(1)
+            } else {
+                Some(focused + 1)
+            };
+            if let Some(other) = other.and_then(|i| stack.nodes.get(i)) {
+                let cmd = format!(
+                    "[con_id={}] swap container with con_id {}; [con_id={}] focus",
+                    stack.nodes[focused].id, other.id, stack.nodes[focused].id
+                );
+                log::debug!("stack main controller, stack move: {}", cmd);
+                self.commands.run_command(cmd).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn stack_move_up(&mut self) -> Result<()> {
+        self.stack_move(true).await
+    }
+
+    pub async fn stack_move_down(&mut self) -> Result<()> {
+        self.stack_move(false).await
+    }
+
+    /// Moves the main window to the top of the stack, leaving the main area
+    /// to the next window that opens. Returns whether there was a main window
+    /// to push.
+    pub async fn stack_push(&mut self) -> Result<bool> {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+
+        if wstree.nodes.len() != 2 {
+            return Ok(false);
+        }
+        let stack = wstree.nodes.first().context("stack container not found")?;
+        let main = wstree.nodes.last().context("main window not found")?;
+        if stack.is_window() || !main.is_window() {
+            return Ok(false);
+        }
+        let top = stack.nodes.first().context("stack is empty")?;
+        let stack_mark = format!("_stack_{}", stack.id);
+        let cmd = format!(
+            "[con_id={}] mark --add {}; [con_id={}] move container to mark {}; [con_mark={}] unmark {}; [con_id={}] swap container with con_id {}; [con_id={}] focus",
+            top.id, stack_mark,
+            main.id, stack_mark,
+            stack_mark, stack_mark,
+            main.id, top.id, main.id
+        );
+        log::debug!("stack main controller, push: {}", cmd);
+        self.commands.run_command(cmd).await?;
+        Ok(true)
+    }
+
+    /// Focuses the stacked window at the given position, counting from 1 at
+    /// the top of the stack.
+    pub async fn stack_focus_index(&mut self, index: usize) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+
+        if let Some(stack) = wstree.nodes.first().filter(|n| !n.is_window()) {
+            let node = stack
+                .nodes
+                .get(index - 1)
+                .with_context(|| format!("the stack has only {} windows", stack.nodes.len()))?;
+            let cmd = format!("[con_id={}] focus", node.id);
+            log::debug!("stack main controller, focus index: {}", cmd);
+            self.commands.run_command(cmd).await?;
         }
         Ok(())
     }
@@ -0,0 +1,112 @@
+use crate::{
+    commands::ListFormat,
+    node_ext::NodeExt,
+    server::{
+        command_runner::{CommandRunner, RunMode},
+        event_handlers::misc::workspace_renamer::get_app_name,
+    },
+    tree_cache::TreeCache,
+    utils::{is_persway_tmp_workspace, is_scratchpad_workspace},
+};
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::sync::Arc;
+use swayipc_async::Node;
+
+/// A window as printed by list-windows.
+#[derive(Serialize)]
+struct Window {
+    id: i64,
+    workspace: String,
+    app_name: String,
+    title: String,
+    role: &'static str,
+    focused: bool,
+}
+
+/// The part a window plays in the layout of its workspace: the main window or
+/// a stacked one in stack_main, a leaf of the tree otherwise.
+fn role(tree: &TreeCache, ws_id: i64, window: &Node, stack_main: bool) -> &'static str {
+    if !stack_main || window.is_floating() {
+        return "leaf";
+    }
+    match tree.parent_of(window.id) {
+        Some(parent) if parent.id == ws_id => "main",
+        _ => "stack",
+    }
+}
+
+pub struct WindowList {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+}
+
+impl WindowList {
+    pub async fn new(tree: Arc<TreeCache>, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::new(mode).await?;
+        Ok(Self { commands, tree })
+    }
+
+    /// Lists the windows of all workspaces from left to right, or the most
+    /// recently focused ones first when given the focus history.
+    pub fn list(
+        &self,
+        format: ListFormat,
+        mru: Option<&[i64]>,
+        stack_main: impl Fn(i32) -> bool,
+    ) -> Result<String> {
+        let mut windows = Vec::new();
+        for ws in self.tree.workspaces() {
+            if is_persway_tmp_workspace(ws) || is_scratchpad_workspace(ws) {
+                continue;
+            }
+            let wstree = self
+                .tree
+                .get(ws.id)
+                .context("workspace not found in tree")?;
+            let mut ws_windows: Vec<&Node> = wstree.iter().filter(|n| n.is_any_window()).collect();
+            ws_windows.reverse();
+            windows.extend(ws_windows.into_iter().map(|window| Window {
+                id: window.id,
+                workspace: ws.name.clone(),
+                app_name: get_app_name(window).unwrap_or_default(),
+                title: window.name.clone().unwrap_or_default(),
+                role: role(&self.tree, ws.id, window, stack_main(ws.num)),
+                focused: window.focused,
+            }));
+        }
+        if let Some(mru) = mru {
+            windows.sort_by_key(|w| mru.iter().position(|id| *id == w.id).unwrap_or(usize::MAX));
+        }
+        match format {
+            ListFormat::Json => {
+                let mut json = serde_json::to_string(&windows)?;
+                json.push('\n');
+                Ok(json)
+            }
+            ListFormat::Dmenu => Ok(windows
+                .iter()
+                .map(|w| {
+                    format!(
+                        "{} {}: {} - {} [{}]\n",
+                        w.id, w.workspace, w.app_name, w.title, w.role
+                    )
+                })
+                .collect()),
+        }
+    }
+
+    /// Focuses the window with the id the selection starts with.
+    pub async fn focus(&mut self, selection: &[String]) -> Result<()> {
+        let first = selection.first().context("no window given")?;
+        let id: i64 = first
+            .parse()
+            .map_err(|_| anyhow!("{} is not a window id", first))?;
+        if !self.tree.get(id).is_some_and(|n| n.is_any_window()) {
+            return Err(anyhow!("no window with id {}", id));
+        }
+        self.commands
+            .run_command(format!("[con_id={}] focus", id))
+            .await
+    }
+}
@@ -0,0 +1,70 @@
+use crate::tree_cache::TreeCache;
+use anyhow::Result;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use swayipc_async::Connection;
+
+/// Controls what happens to the sway commands persway computes.
+#[derive(Clone, Debug)]
+pub enum RunMode {
+    /// Send commands to sway.
+    Execute,
+    /// Only log the commands that would have been sent.
+    DryRun,
+    /// Record the commands that would have been sent, see `persway explain`.
+    Explain(Arc<Mutex<Vec<String>>>),
+}
+
+impl RunMode {
+    pub fn is_explain(&self) -> bool {
+        matches!(self, Self::Explain(_))
+    }
+}
+
+pub struct CommandRunner {
+    connection: Connection,
+    mode: RunMode,
+    stale: Option<Arc<AtomicBool>>,
+}
+
+impl CommandRunner {
+    pub async fn new(mode: RunMode) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            mode,
+            stale: None,
+        })
+    }
+
+    /// A runner for commands computed from the tree, which marks the tree as
+    /// stale once it sent one, see `TreeCache::is_stale`.
+    pub async fn for_tree(mode: RunMode, tree: &TreeCache) -> Result<Self> {
+        let mut runner = Self::new(mode).await?;
+        runner.stale = Some(tree.stale_flag());
+        Ok(runner)
+    }
+
+    /// The underlying connection, meant for queries only. Commands should go
+    /// through `run_command` so that they respect the run mode.
+    pub fn connection(&mut self) -> &mut Connection {
+        &mut self.connection
+    }
+
+    pub async fn run_command<T: AsRef<str>>(&mut self, cmd: T) -> Result<()> {
+        let cmd = cmd.as_ref();
+        match &self.mode {
+            RunMode::Execute => {
+                if let Some(stale) = &self.stale {
+                    stale.store(true, Ordering::SeqCst);
+                }
+                self.connection.run_command(cmd).await?;
+            }
+            RunMode::DryRun => log::info!("dry-run: {}", cmd),
+            RunMode::Explain(commands) => commands.lock().unwrap().push(cmd.to_string()),
+        }
+        Ok(())
+    }
+}
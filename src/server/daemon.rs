@@ -1,16 +1,15 @@
 use super::message_handler::MessageHandler;
 use crate::commands::PerswayCommand;
-use crate::layout::WorkspaceLayout;
 use crate::Args;
 use crate::{commands::DaemonArgs, utils};
 use anyhow::{anyhow, Result};
 use async_std::os::unix::net::{UnixListener, UnixStream};
 use async_std::prelude::*;
 use async_std::task;
-use clap::Parser;
-use futures::channel::mpsc;
+use clap::{CommandFactory, FromArgMatches};
+use futures::channel::{mpsc, oneshot};
 use futures::SinkExt;
-use futures::{select, stream::StreamExt};
+use futures::{select, stream::StreamExt, FutureExt};
 use signal_hook::consts::signal::*;
 use signal_hook_async_std::Signals;
 use std::process::exit;
@@ -19,8 +18,9 @@ use swayipc_async::{Connection, Event, EventType, WindowEvent};
 pub type Sender<T> = mpsc::UnboundedSender<T>;
 
 pub enum Message {
-    WindowEvent(Box<WindowEvent>),
-    CommandEvent(PerswayCommand),
+    WindowEvents(Vec<WindowEvent>),
+    CommandEvent(Box<PerswayCommand>, oneshot::Sender<String>),
+    Subscribe(Sender<String>),
 }
 
 pub struct Daemon {
@@ -32,34 +32,10 @@ pub struct Daemon {
 impl Daemon {
     pub fn new(args: DaemonArgs, socket_path: Option<String>) -> Daemon {
         let socket_path = utils::get_socket_path(socket_path);
-        let DaemonArgs {
-            default_layout,
-            stack_main_default_size,
-            stack_main_default_stack_layout,
-            workspace_renaming,
-            on_window_focus,
-            on_window_focus_leave,
-            on_exit,
-            ..
-        } = args;
-        {
-            let default_layout = match default_layout {
-                WorkspaceLayout::StackMain { .. } => WorkspaceLayout::StackMain {
-                    size: stack_main_default_size,
-                    stack_layout: stack_main_default_stack_layout,
-                },
-                _ => default_layout,
-            };
-            Daemon {
-                socket_path,
-                on_exit,
-                message_handler: MessageHandler::new(
-                    default_layout,
-                    workspace_renaming,
-                    on_window_focus,
-                    on_window_focus_leave,
-                ),
-            }
+        Daemon {
+            socket_path,
+            on_exit: args.on_exit.clone(),
+            message_handler: MessageHandler::from_args(args),
         }
     }
 
@@ -110,14 +86,20 @@ impl Daemon {
         loop {
             select! {
                 event = sway_events.select_next_some() => {
-                        match event? {
-                            Event::Window(event) => {
-                                log::debug!("select: sway event sending through channel");
-                                sender.send(Message::WindowEvent(event)).await?;
-                                log::debug!("select: sway event sent through channel");
-                            },
-                            _ => unreachable!(),
+                        let mut events = vec![event?];
+                        while let Some(Some(event)) = sway_events.next().now_or_never() {
+                            events.push(event?);
                         }
+                        let events = events
+                            .into_iter()
+                            .map(|event| match event {
+                                Event::Window(event) => *event,
+                                _ => unreachable!(),
+                            })
+                            .collect();
+                        log::debug!("select: sway events sending through channel");
+                        sender.send(Message::WindowEvents(events)).await?;
+                        log::debug!("select: sway events sent through channel");
                 },
                 stream = incoming.select_next_some() => {
                         let stream = stream?;
@@ -128,16 +110,29 @@ impl Daemon {
                 message = receiver.select_next_some() => {
                     log::debug!("select: received message");
                     match message {
-                        Message::WindowEvent(event) => {
-                          log::debug!("select: handling message window event");
-                          self.message_handler.handle_event(event).await?;
-                          log::debug!("select: handled message window event");
+                        Message::WindowEvents(events) => {
+                          log::debug!("select: handling message window events");
+                          if let Err(e) = self.message_handler.handle_events(events).await {
+                              log::error!("handling window events failed: {}", e);
+                          }
+                          log::debug!("select: handled message window events");
                         },
-                        Message::CommandEvent(command) => {
+                        Message::CommandEvent(command, reply) => {
                           log::debug!("select: handling message command event");
-                          self.message_handler.handle_command(command).await?;
+                          let response = match self.message_handler.handle_command(*command).await {
+                              Ok(response) => response.unwrap_or_else(|| String::from("success\n")),
+                              Err(e) => {
+                                  log::error!("command failed: {}", e);
+                                  format!("fail: {}\n", e)
+                              }
+                          };
+                          let _ = reply.send(response);
                           log::debug!("select: handled message command event");
                         }
+                        Message::Subscribe(subscriber) => {
+                          log::debug!("select: handling message subscribe");
+                          self.message_handler.subscribe(subscriber)?;
+                        }
                     };
                     log::debug!("select: handled message");
                 }
@@ -152,17 +147,35 @@ impl Daemon {
         match stream.read_to_string(&mut message).await {
             Ok(_) => {
                 log::debug!("got message: {}", message);
-                let args = match Args::try_parse_from(message.split_ascii_whitespace()) {
-                    Ok(args) => args,
-                    Err(e) => {
-                        log::error!("unknown message: {}\n{}", message, e);
-                        return Err(anyhow!("unknown message"));
+                let matches =
+                    match Args::command().try_get_matches_from(message.split_ascii_whitespace()) {
+                        Ok(matches) => matches,
+                        Err(e) => {
+                            log::error!("unknown message: {}\n{}", message, e);
+                            return Err(anyhow!("unknown message"));
+                        }
+                    };
+                let mut args = Args::from_arg_matches(&matches)?;
+                args.command.note_given_options(&matches);
+                if let PerswayCommand::Subscribe = args.command {
+                    let (subscriber, mut status) = mpsc::unbounded();
+                    sender.send(Message::Subscribe(subscriber)).await?;
+                    while let Some(status) = status.next().await {
+                        if stream.write_all(status.as_bytes()).await.is_err() {
+                            log::debug!("subscriber went away");
+                            break;
+                        }
                     }
-                };
+                    return Ok(());
+                }
                 log::debug!("sending command through channel");
-                sender.send(Message::CommandEvent(args.command)).await?;
-                log::debug!("writing success message back to client");
-                stream.write_all(b"success\n").await?;
+                let (reply, response) = oneshot::channel();
+                sender
+                    .send(Message::CommandEvent(Box::new(args.command), reply))
+                    .await?;
+                let response = response.await?;
+                log::debug!("writing response back to client");
+                stream.write_all(response.as_bytes()).await?;
             }
             Err(e) => {
                 log::error!("Invalid UTF-8 sequence: {}", e);
@@ -1,2 +1,6 @@
+pub mod bsp;
+pub mod columns;
+pub mod monocle;
+pub mod scrolling;
 pub mod spiral;
 pub mod stack_main;
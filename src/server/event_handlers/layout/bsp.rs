@@ -0,0 +1,142 @@
+use super::super::traits::WindowEventHandler;
+use crate::server::command_runner::{CommandRunner, RunMode};
+use crate::{
+    layout::PreselDirection,
+    node_ext::NodeExt,
+    tree_cache::TreeCache,
+    utils::{is_persway_tmp_workspace, is_scratchpad_workspace},
+};
+
+use anyhow::Result;
+use std::sync::Arc;
+use swayipc_async::{Node, WindowChange, WindowEvent, Workspace};
+
+fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
+    is_persway_tmp_workspace(workspace) || is_scratchpad_workspace(workspace)
+}
+
+/// The split that fits the window best, along its longer side.
+pub fn longer_side(node: &Node) -> &'static str {
+    if node.rect.height > node.rect.width {
+        "v"
+    } else {
+        "h"
+    }
+}
+
+/// A split chosen with `persway presel` for the next window opened next to
+/// the given one.
+#[derive(Clone, Debug)]
+pub struct Presel {
+    pub window: i64,
+    pub direction: PreselDirection,
+}
+
+impl Presel {
+    pub fn split(&self) -> &'static str {
+        match self.direction {
+            PreselDirection::Up | PreselDirection::Down => "v",
+            _ => "h",
+        }
+    }
+
+    /// Whether the window was opened next to the preselected one.
+    pub fn applies_to(&self, tree: &TreeCache, id: i64) -> bool {
+        id != self.window
+            && tree
+                .parent_of(id)
+                .is_some_and(|parent| parent.nodes.iter().any(|n| n.id == self.window))
+    }
+}
+
+pub struct Bsp {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    presel: Option<Presel>,
+}
+
+impl Bsp {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        presel: Option<Presel>,
+        mode: RunMode,
+    ) {
+        if let Ok(mut manager) = Self::new(tree, presel, mode).await {
+            manager.handle(event).await;
+        }
+    }
+
+    pub async fn new(tree: Arc<TreeCache>, presel: Option<Presel>, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            presel,
+        })
+    }
+
+    /// Splits the focused window so the next window opens next to it, along
+    /// its longer side unless a split was preselected for it.
+    async fn split(&mut self, event: WindowEvent) -> Result<()> {
+        let tree = &self.tree;
+        let node = if let Some(node) = tree.get(event.container.id) {
+            node
+        } else {
+            log::warn!("bsp layout, no node found with id {}", event.container.id);
+            return Ok(());
+        };
+        let ws = node.get_workspace(tree)?;
+        if should_skip_layout_of_workspace(&ws)
+            || node.is_floating_window()
+            || node.is_floating_container()
+            || node.is_full_screen()
+            || node.is_stacked(tree)?
+            || node.is_tabbed(tree)?
+        {
+            return Ok(());
+        }
+        let split = match &self.presel {
+            Some(presel) if presel.window == node.id => presel.split(),
+            _ => longer_side(node),
+        };
+        let cmd = format!("[con_id={}] focus; split {}", node.id, split);
+        log::debug!("bsp layout: {}", cmd);
+        self.commands.run_command(cmd).await
+    }
+
+    /// Moves a window opened next to the preselected one before it when the
+    /// preselection asked for the left or upper side.
+    async fn place(&mut self, event: WindowEvent) -> Result<()> {
+        let presel = match &self.presel {
+            Some(presel) if presel.applies_to(&self.tree, event.container.id) => presel,
+            _ => return Ok(()),
+        };
+        let direction = match presel.direction {
+            PreselDirection::Left => "left",
+            PreselDirection::Up => "up",
+            _ => return Ok(()),
+        };
+        let cmd = format!("[con_id={}] move {}", event.container.id, direction);
+        log::debug!("bsp layout: {}", cmd);
+        self.commands.run_command(cmd).await
+    }
+}
+
+impl WindowEventHandler for Bsp {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        match event.change {
+            WindowChange::Focus => {
+                if let Err(e) = self.split(*event).await {
+                    log::error!("bsp manager, split err: {}", e);
+                };
+            }
+            WindowChange::New => {
+                if let Err(e) = self.place(*event).await {
+                    log::error!("bsp manager, presel err: {}", e);
+                };
+            }
+            _ => log::debug!("bsp manager, not handling event: {:?}", event.change),
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use super::super::traits::WindowEventHandler;
+use crate::server::command_runner::{CommandRunner, RunMode};
+use crate::{
+    node_ext::NodeExt,
+    tree_cache::TreeCache,
+    utils::{is_persway_tmp_workspace, is_scratchpad_workspace},
+};
+
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use swayipc_async::{Node, NodeLayout, WindowChange, WindowEvent, Workspace};
+
+fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
+    is_persway_tmp_workspace(workspace) || is_scratchpad_workspace(workspace)
+}
+
+/// Whether windows are lined up in columns or in rows.
+#[derive(Clone, Copy, Debug)]
+pub enum Lines {
+    Columns,
+    Rows,
+}
+
+impl Lines {
+    fn layout(self) -> NodeLayout {
+        match self {
+            Self::Columns => NodeLayout::SplitH,
+            Self::Rows => NodeLayout::SplitV,
+        }
+    }
+
+    fn split(self) -> &'static str {
+        match self {
+            Self::Columns => "h",
+            Self::Rows => "v",
+        }
+    }
+
+    /// Where a full line wraps to.
+    fn wrap(self) -> &'static str {
+        match self {
+            Self::Columns => "down",
+            Self::Rows => "right",
+        }
+    }
+}
+
+/// A line of windows. In a workspace with a single line that is the workspace
+/// itself, otherwise it is a container or a lone window.
+struct Line {
+    id: i64,
+    windows: Vec<i64>,
+}
+
+fn windows_in_order(node: &Node, windows: &mut Vec<i64>) {
+    for child in &node.nodes {
+        if child.is_window() {
+            windows.push(child.id);
+        } else {
+            windows_in_order(child, windows);
+        }
+    }
+}
+
+pub struct Columns {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    lines: Lines,
+    max: Option<u8>,
+}
+
+impl Columns {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        lines: Lines,
+        max: Option<u8>,
+        mode: RunMode,
+    ) {
+        if let Ok(mut manager) = Self::new(tree, lines, max, mode).await {
+            manager.handle(event).await;
+        }
+    }
+
+    pub async fn new(
+        tree: Arc<TreeCache>,
+        lines: Lines,
+        max: Option<u8>,
+        mode: RunMode,
+    ) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            lines,
+            max,
+        })
+    }
+
+    /// Returns whether the workspace has more than one line, and the lines
+    /// without the excluded window.
+    fn lines_of(&self, wstree: &Node, exclude: Option<i64>) -> (bool, Vec<Line>) {
+        let single = wstree.layout == self.lines.layout()
+            || (wstree.nodes.len() == 1 && wstree.nodes[0].is_window());
+        let lines = if single {
+            vec![wstree]
+        } else {
+            wstree.nodes.iter().collect()
+        };
+        let lines = lines
+            .into_iter()
+            .map(|line| {
+                let mut windows = Vec::new();
+                if line.is_window() {
+                    windows.push(line.id);
+                } else {
+                    windows_in_order(line, &mut windows);
+                }
+                windows.retain(|&id| Some(id) != exclude);
+                Line {
+                    id: line.id,
+                    windows,
+                }
+            })
+            .filter(|line| !line.windows.is_empty())
+            .collect();
+        (!single, lines)
+    }
+
+    fn move_after(&self, id: i64, target: i64) -> String {
+        let mark = format!("_line_{}", target);
+        format!(
+            "[con_id={}] mark --add {}; [con_id={}] move container to mark {}; [con_mark={}] unmark {}; ",
+            target, mark, id, mark, mark, mark
+        )
+    }
+
+    /// Places a window that arrived on the workspace. A window overflowing
+    /// its line is moved to the end of the last line, or into a new line when
+    /// that is full too.
+    async fn on_new_window(&mut self, id: i64) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let node = if let Some(node) = tree.get(id) {
+            node
+        } else {
+            log::warn!("no node found with id {}", id);
+            return Ok(());
+        };
+        let ws = node.get_workspace(&tree)?;
+        if should_skip_layout_of_workspace(&ws) || node.is_floating() || node.is_full_screen() {
+            return Ok(());
+        }
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+        if wstree.nodes.len() == 1 && wstree.nodes[0].id == id {
+            if wstree.layout != self.lines.layout() {
+                let cmd = format!("[con_id={}] split {}", id, self.lines.split());
+                self.commands.run_command(cmd).await?;
+            }
+            return Ok(());
+        }
+        let max = match self.max {
+            Some(max) => max as usize,
+            None => return self.rebalance(ws.id).await,
+        };
+        let (multiple, lines) = self.lines_of(wstree, None);
+        let line = match lines.iter().find(|line| line.windows.contains(&id)) {
+            Some(line) => line,
+            None => return self.rebalance(ws.id).await,
+        };
+        // A window opened next to a line rather than in it starts a line of
+        // its own, which is only right when the last line is full.
+        let alone = multiple && line.id == id;
+        if !alone && line.windows.len() <= max {
+            return self.rebalance(ws.id).await;
+        }
+        let (multiple, others) = self.lines_of(wstree, Some(id));
+        let last = others.last().context("no lines left")?;
+        let last_window = *last.windows.last().context("empty line")?;
+        let mut cmd = String::new();
+        if alone && last.windows.len() >= max {
+            return self.rebalance(ws.id).await;
+        } else if last.windows.len() < max {
+            if multiple && last.id == last_window {
+                cmd.push_str(&format!(
+                    "[con_id={}] split {}; ",
+                    last_window,
+                    self.lines.split()
+                ));
+            }
+            cmd.push_str(&self.move_after(id, last_window));
+        } else {
+            cmd.push_str(&self.move_after(id, last_window));
+            cmd.push_str(&format!("[con_id={}] move {}", id, self.lines.wrap()));
+        }
+        log::debug!("columns layout: {}", cmd);
+        self.commands
+            .run_command(cmd.trim_end_matches("; "))
+            .await?;
+        self.refresh().await?;
+        self.rebalance(ws.id).await
+    }
+
+    /// Fills the gap a window left behind by pulling windows up from the
+    /// following lines.
+    async fn on_close_window(&mut self, ws_id: i64, id: i64) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.workspace_of(ws_id).context("workspace not found")?;
+        if should_skip_layout_of_workspace(ws) {
+            return Ok(());
+        }
+        let wstree = tree.get(ws_id).context("workspace not found in tree")?;
+        let max = match self.max {
+            Some(max) => max as usize,
+            None => return self.rebalance(ws_id).await,
+        };
+        let (multiple, mut lines) = self.lines_of(wstree, Some(id));
+        let mut cmd = String::new();
+        let mut i = 0;
+        while i + 1 < lines.len() {
+            if lines[i].windows.len() >= max {
+                i += 1;
+                continue;
+            }
+            let window = lines[i + 1].windows.remove(0);
+            let target = *lines[i].windows.last().context("empty line")?;
+            if multiple && lines[i].id == target {
+                cmd.push_str(&format!(
+                    "[con_id={}] split {}; ",
+                    target,
+                    self.lines.split()
+                ));
+                lines[i].id = -1;
+            }
+            cmd.push_str(&self.move_after(window, target));
+            lines[i].windows.push(window);
+            if lines[i + 1].windows.is_empty() {
+                lines.remove(i + 1);
+            }
+        }
+        if !cmd.is_empty() {
+            log::debug!("columns layout: {}", cmd);
+            self.commands
+                .run_command(cmd.trim_end_matches("; "))
+                .await?;
+            self.refresh().await?;
+        }
+        self.rebalance(ws_id).await
+    }
+
+    async fn refresh(&mut self) -> Result<()> {
+        self.tree = Arc::new(TreeCache::fetch(self.commands.connection()).await?);
+        Ok(())
+    }
+
+    /// Gives all windows of a line, and all lines, the same size.
+    async fn rebalance(&mut self, ws_id: i64) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let wstree = tree.get(ws_id).context("workspace not found in tree")?;
+        let (multiple, lines) = self.lines_of(wstree, None);
+        let horizontal = matches!(self.lines, Lines::Columns);
+        let mut cmd = String::new();
+        for line in &lines {
+            if let Some(node) = tree.get(line.id) {
+                cmd.push_str(&equalize(&node.nodes, horizontal));
+            }
+        }
+        if multiple {
+            cmd.push_str(&equalize(&wstree.nodes, !horizontal));
+        }
+        if !cmd.is_empty() {
+            log::debug!("columns layout rebalance: {}", cmd);
+            self.commands
+                .run_command(cmd.trim_end_matches("; "))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Resize commands giving the nodes equal sizes.
+fn equalize(nodes: &[Node], horizontal: bool) -> String {
+    let extent = |n: &Node| {
+        if horizontal {
+            n.rect.width
+        } else {
+            n.rect.height
+        }
+    };
+    let total: i32 = nodes.iter().map(extent).sum();
+    let count = nodes.len() as i32;
+    let sizes: Vec<i32> = (0..count)
+        .map(|i| total * (i + 1) / count - total * i / count)
+        .collect();
+    resize_to(nodes, &sizes, horizontal)
+}
+
+/// Resize commands giving the nodes the given sizes in pixels. Each node's far
+/// edge is moved into place in turn, which only affects the node after it.
+pub fn resize_to(nodes: &[Node], sizes: &[i32], horizontal: bool) -> String {
+    let extent = |n: &Node| {
+        if horizontal {
+            n.rect.width
+        } else {
+            n.rect.height
+        }
+    };
+    let edge = if horizontal { "right" } else { "down" };
+    let mut cmd = String::new();
+    let mut current = 0;
+    let mut wanted = 0;
+    for (node, size) in nodes.iter().zip(sizes).take(nodes.len().saturating_sub(1)) {
+        current += extent(node);
+        wanted += size;
+        let delta = wanted - current;
+        if delta > 1 {
+            cmd.push_str(&format!(
+                "[con_id={}] resize grow {} {} px; ",
+                node.id, edge, delta
+            ));
+        } else if delta < -1 {
+            cmd.push_str(&format!(
+                "[con_id={}] resize shrink {} {} px; ",
+                node.id, edge, -delta
+            ));
+        }
+    }
+    cmd
+}
+
+impl WindowEventHandler for Columns {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        let id = event.container.id;
+        let result = match event.change {
+            WindowChange::New => self.on_new_window(id).await,
+            WindowChange::Close => match self.tree.focused_workspace() {
+                Some(ws) => self.on_close_window(ws.id, id).await,
+                None => Ok(()),
+            },
+            WindowChange::Move => {
+                let focused = self.tree.focused_workspace().map(|ws| ws.id);
+                let ws = self.tree.workspace_of(id).map(|ws| ws.id);
+                match (focused, ws) {
+                    (Some(focused), Some(ws)) if focused != ws => {
+                        match self.on_new_window(id).await {
+                            Ok(()) => self.on_close_window(focused, id).await,
+                            Err(e) => Err(e),
+                        }
+                    }
+                    (Some(focused), None) => self.on_close_window(focused, id).await,
+                    _ => self.on_new_window(id).await,
+                }
+            }
+            WindowChange::Floating => match self.tree.workspace_of(id).map(|ws| ws.id) {
+                Some(ws) if event.container.is_floating() => self.on_close_window(ws, id).await,
+                Some(_) => self.on_new_window(id).await,
+                None => Ok(()),
+            },
+            _ => {
+                log::debug!("columns not handling event: {:?}", event.change);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            log::error!("columns layout err: {}", e);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use super::super::traits::WindowEventHandler;
+use crate::server::command_runner::{CommandRunner, RunMode};
+use crate::{
+    node_ext::NodeExt,
+    tree_cache::TreeCache,
+    utils::{self, is_persway_tmp_workspace, is_scratchpad_workspace},
+};
+
+use anyhow::{Context, Result};
+use async_std::task;
+use std::{sync::Arc, time::Duration};
+use swayipc_async::{Node, WindowChange, WindowEvent, Workspace};
+
+pub struct Monocle {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    mode: RunMode,
+}
+
+fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
+    is_persway_tmp_workspace(workspace) || is_scratchpad_workspace(workspace)
+}
+
+/// Whether no window is nested deeper than the tabbed container, which is the
+/// workspace or the one container sway wraps its children in when making the
+/// workspace tabbed.
+fn is_flat(ws: &Node) -> bool {
+    let tabs = match ws.nodes.as_slice() {
+        [only] if !only.is_window() => &only.nodes,
+        nodes => nodes,
+    };
+    tabs.iter().all(|n| n.is_window())
+}
+
+impl Monocle {
+    pub async fn handle(event: Box<WindowEvent>, tree: Arc<TreeCache>, mode: RunMode) {
+        if let Ok(mut manager) = Self::new(tree, mode).await {
+            manager.handle(event).await;
+        }
+    }
+
+    pub async fn new(tree: Arc<TreeCache>, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode.clone(), &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            mode,
+        })
+    }
+
+    /// Moves the windows out of the workspace and back in their order, so that
+    /// they end up side by side in its tabbed container instead of nested in
+    /// splits. The given window is focused afterwards.
+    async fn flatten(&self, ws_num: i32, focus: i64) -> Result<()> {
+        utils::relayout_workspace(
+            ws_num,
+            self.mode.clone(),
+            move |mut conn, ws_num, _old_ws_id, _output_id, windows| async move {
+                for window in windows.iter().rev() {
+                    let cmd = format!(
+                        "[con_id={}] move to workspace number {}; [con_id={}] focus",
+                        window.id, ws_num, window.id
+                    );
+                    log::debug!("monocle flatten cmd: {}", cmd);
+                    conn.run_command(cmd).await?;
+                    task::sleep(Duration::from_millis(50)).await;
+                }
+                conn.run_command(format!("[con_id={}] focus", focus)).await
+            },
+        )
+        .await
+    }
+
+    async fn layout(&mut self, event: WindowEvent) -> Result<()> {
+        let tree = &self.tree;
+        let node = if let Some(node) = tree.get(event.container.id) {
+            node
+        } else {
+            log::warn!(
+                "monocle layout, no node found with id {}",
+                event.container.id
+            );
+            return Ok(());
+        };
+        let ws = node.get_workspace(tree)?;
+        if should_skip_layout_of_workspace(&ws) {
+            log::debug!("skip monocle layout of \"special\" workspace");
+            return Ok(());
+        }
+        if node.is_floating_window() || node.is_floating_container() {
+            return Ok(());
+        }
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+        if !is_flat(wstree) {
+            log::debug!("monocle layout, flattening workspace {}", ws.num);
+            return self.flatten(ws.num, node.id).await;
+        }
+        if node.is_tabbed(tree)? {
+            return Ok(());
+        }
+        let cmd = format!("[con_id={}] layout tabbed", node.id);
+        log::debug!("monocle layout: {}", cmd);
+        self.commands.run_command(cmd).await
+    }
+}
+
+impl WindowEventHandler for Monocle {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        match event.change {
+            WindowChange::New | WindowChange::Move | WindowChange::Floating => {
+                if let Err(e) = self.layout(*event).await {
+                    log::error!("monocle manager, layout err: {}", e);
+                };
+            }
+            _ => log::debug!("monocle manager, not handling event: {:?}", event.change),
+        }
+    }
+}
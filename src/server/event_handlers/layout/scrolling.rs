@@ -0,0 +1,314 @@
+use super::super::traits::WindowEventHandler;
+use super::columns::resize_to;
+use crate::server::command_runner::{CommandRunner, RunMode};
+use crate::{
+    tree_cache::TreeCache,
+    utils::{is_persway_tmp_workspace, is_scratchpad_workspace},
+};
+
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use swayipc_async::{Node, NodeLayout, WindowChange, WindowEvent, Workspace};
+
+/// Percent of the workspace each container of off-screen columns keeps.
+const PEEK_WIDTH: i32 = 5;
+
+fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
+    is_persway_tmp_workspace(workspace) || is_scratchpad_workspace(workspace)
+}
+
+fn stash_mark(side: &str, ws_id: i64) -> String {
+    format!("_scroll_{}_{}", side, ws_id)
+}
+
+/// Follows the focus stack of the node down to the window that was focused
+/// last within it.
+pub fn focused_leaf(tree: &TreeCache, node: &Node) -> i64 {
+    let mut current = node;
+    while let Some(next) = current
+        .focus
+        .iter()
+        .find(|id| current.nodes.iter().any(|n| n.id == **id))
+        .and_then(|&id| tree.get(id))
+    {
+        current = next;
+    }
+    current.id
+}
+
+/// The columns of a scrolling workspace from left to right. The columns
+/// scrolled out of view on either side are kept in a marked tabbed container.
+pub struct Strip {
+    pub left: Vec<i64>,
+    pub visible: Vec<i64>,
+    pub right: Vec<i64>,
+    /// Index of the column that was focused last.
+    pub focused: Option<usize>,
+}
+
+impl Strip {
+    pub fn of(wstree: &Node) -> Self {
+        let left_mark = stash_mark("left", wstree.id);
+        let right_mark = stash_mark("right", wstree.id);
+        let mut strip = Strip {
+            left: Vec::new(),
+            visible: Vec::new(),
+            right: Vec::new(),
+            focused: None,
+        };
+        let focused = wstree
+            .focus
+            .iter()
+            .find_map(|id| wstree.nodes.iter().find(|n| n.id == *id));
+        let mut focused_column = None;
+        for node in &wstree.nodes {
+            let stash = if node.marks.contains(&left_mark) {
+                Some(&mut strip.left)
+            } else if node.marks.contains(&right_mark) {
+                Some(&mut strip.right)
+            } else {
+                None
+            };
+            match stash {
+                Some(stash) => {
+                    stash.extend(node.nodes.iter().map(|n| n.id));
+                    if focused.map(|f| f.id) == Some(node.id) {
+                        focused_column = node
+                            .focus
+                            .iter()
+                            .find(|id| node.nodes.iter().any(|n| n.id == **id))
+                            .copied();
+                    }
+                }
+                None => {
+                    strip.visible.push(node.id);
+                    if focused.map(|f| f.id) == Some(node.id) {
+                        focused_column = Some(node.id);
+                    }
+                }
+            }
+        }
+        strip.focused = focused_column.and_then(|id| strip.columns().position(|c| c == id));
+        strip
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = i64> + '_ {
+        self.left
+            .iter()
+            .chain(&self.visible)
+            .chain(&self.right)
+            .copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.left.len() + self.visible.len() + self.right.len()
+    }
+}
+
+/// Moves a column into a stash. The first column to go there is wrapped in a
+/// new tabbed container instead, its id is returned as the container needs to
+/// be marked once it exists.
+fn stash(cmd: &mut String, id: i64, dir: &str, create: bool) -> Option<i64> {
+    if create {
+        cmd.push_str(&format!(
+            "[con_id={}] split v; [con_id={}] layout tabbed; ",
+            id, id
+        ));
+        Some(id)
+    } else {
+        cmd.push_str(&format!("[con_id={}] move {}; ", id, dir));
+        None
+    }
+}
+
+pub struct Scrolling {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    width: u8,
+}
+
+impl Scrolling {
+    pub async fn handle(event: Box<WindowEvent>, tree: Arc<TreeCache>, width: u8, mode: RunMode) {
+        if let Ok(mut manager) = Self::new(tree, width, mode).await {
+            manager.handle(event).await;
+        }
+    }
+
+    pub async fn new(tree: Arc<TreeCache>, width: u8, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            width,
+        })
+    }
+
+    /// Scrolls the workspace so that the focused column is in view, showing
+    /// as many columns of the configured width as fit.
+    pub async fn arrange(&mut self, ws_id: i64) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.workspace_of(ws_id).context("workspace not found")?;
+        if should_skip_layout_of_workspace(ws) {
+            return Ok(());
+        }
+        let wstree = tree.get(ws_id).context("workspace not found in tree")?;
+        if wstree.nodes.len() == 1 && wstree.layout != NodeLayout::SplitH {
+            let cmd = format!("[con_id={}] split h", wstree.nodes[0].id);
+            return self.commands.run_command(cmd).await;
+        }
+        let mut strip = Strip::of(wstree);
+        if strip.len() == 0 {
+            return Ok(());
+        }
+        let fits = (100 / self.width as usize).max(1);
+        let focused = strip.focused.unwrap_or(strip.left.len());
+        let mut start = strip.left.len();
+        if focused < start {
+            start = focused;
+        } else if focused >= start + fits {
+            start = focused + 1 - fits;
+        }
+        let start = start.min(strip.len().saturating_sub(fits));
+
+        let mut cmd = String::new();
+        let mut created = Vec::new();
+        // With nothing in view the stashes are next to each other, so moving a
+        // column out of one would move it into the other.
+        if strip.visible.is_empty() && !strip.left.is_empty() && !strip.right.is_empty() {
+            for id in std::mem::take(&mut strip.right) {
+                cmd.push_str(&format!("[con_id={}] move left; ", id));
+                strip.left.push(id);
+            }
+        }
+        while strip.left.len() > start {
+            let id = strip.left.pop().unwrap();
+            cmd.push_str(&format!("[con_id={}] move right; ", id));
+            strip.visible.insert(0, id);
+        }
+        while strip.left.len() < start {
+            if strip.visible.is_empty() {
+                let id = strip.right.remove(0);
+                cmd.push_str(&format!("[con_id={}] move left; ", id));
+                strip.visible.push(id);
+            }
+            let id = strip.visible.remove(0);
+            if let Some(id) = stash(&mut cmd, id, "left", strip.left.is_empty()) {
+                created.push((id, stash_mark("left", ws_id)));
+            }
+            strip.left.push(id);
+        }
+        while strip.visible.len() > fits {
+            let id = strip.visible.pop().unwrap();
+            if let Some(id) = stash(&mut cmd, id, "right", strip.right.is_empty()) {
+                created.push((id, stash_mark("right", ws_id)));
+            }
+            strip.right.insert(0, id);
+        }
+        while strip.visible.len() < fits && !strip.right.is_empty() {
+            let id = strip.right.remove(0);
+            cmd.push_str(&format!("[con_id={}] move left; ", id));
+            strip.visible.push(id);
+        }
+        if !cmd.is_empty() {
+            log::debug!("scrolling layout: {}", cmd);
+            self.commands
+                .run_command(cmd.trim_end_matches("; "))
+                .await?;
+            self.tree = Arc::new(TreeCache::fetch(self.commands.connection()).await?);
+        }
+        let marks: String = created
+            .iter()
+            .filter_map(|(id, mark)| {
+                let stash = self.tree.parent_of(*id).filter(|p| p.id != ws_id)?;
+                Some(format!("[con_id={}] mark --add {}; ", stash.id, mark))
+            })
+            .collect();
+        if !marks.is_empty() {
+            log::debug!("scrolling layout: {}", marks);
+            self.commands
+                .run_command(marks.trim_end_matches("; "))
+                .await?;
+            self.tree = Arc::new(TreeCache::fetch(self.commands.connection()).await?);
+        }
+        self.resize(ws_id).await
+    }
+
+    /// Gives the columns in view the configured width, the stashes share what
+    /// is left. Without stashes the columns share the whole workspace.
+    async fn resize(&mut self, ws_id: i64) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let wstree = tree.get(ws_id).context("workspace not found in tree")?;
+        let strip = Strip::of(wstree);
+        let stashes = wstree.nodes.len() - strip.visible.len();
+        let visible = strip.visible.len() as i32;
+        if visible == 0 {
+            return Ok(());
+        }
+        let total: i32 = wstree.nodes.iter().map(|n| n.rect.width).sum();
+        let sizes: Vec<i32> = if stashes == 0 {
+            (0..visible)
+                .map(|i| total * (i + 1) / visible - total * i / visible)
+                .collect()
+        } else {
+            let stashes = stashes as i32;
+            let column = (total * self.width as i32 / 100)
+                .min((total - total * PEEK_WIDTH * stashes / 100) / visible);
+            let stash = (total - column * visible) / stashes;
+            wstree
+                .nodes
+                .iter()
+                .map(|n| {
+                    if strip.visible.contains(&n.id) {
+                        column
+                    } else {
+                        stash
+                    }
+                })
+                .collect()
+        };
+        let cmd = resize_to(&wstree.nodes, &sizes, true);
+        if !cmd.is_empty() {
+            log::debug!("scrolling layout resize: {}", cmd);
+            self.commands
+                .run_command(cmd.trim_end_matches("; "))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl WindowEventHandler for Scrolling {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        let id = event.container.id;
+        let focused = self.tree.focused_workspace().map(|ws| ws.id);
+        let result = match event.change {
+            WindowChange::New
+            | WindowChange::Focus
+            | WindowChange::Move
+            | WindowChange::Floating => {
+                let ws = self.tree.workspace_of(id).map(|ws| ws.id);
+                let mut result = Ok(());
+                if let Some(ws) = ws {
+                    result = self.arrange(ws).await;
+                }
+                match focused {
+                    Some(focused) if ws != Some(focused) && result.is_ok() => {
+                        self.arrange(focused).await
+                    }
+                    _ => result,
+                }
+            }
+            WindowChange::Close => match focused {
+                Some(focused) => self.arrange(focused).await,
+                None => Ok(()),
+            },
+            _ => {
+                log::debug!("scrolling not handling event: {:?}", event.change);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            log::error!("scrolling layout err: {}", e);
+        }
+    }
+}
@@ -1,14 +1,29 @@
 use super::super::traits::WindowEventHandler;
+use crate::server::command_runner::{CommandRunner, RunMode};
 use crate::{
+    layout::{SplitDirection, StackLayout},
     node_ext::NodeExt,
+    tree_cache::TreeCache,
     utils::{is_persway_tmp_workspace, is_scratchpad_workspace},
 };
 
 use anyhow::Result;
-use swayipc_async::{Connection, WindowChange, WindowEvent, Workspace};
+use std::sync::Arc;
+use swayipc_async::{Node, NodeLayout, WindowChange, WindowEvent, Workspace};
+
+/// Options shared by the spiral and dwindle layouts.
+#[derive(Clone, Debug)]
+pub struct SpiralOptions {
+    pub ratio: Option<u8>,
+    pub direction: Option<SplitDirection>,
+    pub max_depth: Option<u8>,
+    pub overflow_layout: StackLayout,
+}
 
 pub struct Spiral {
-    connection: Connection,
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    options: SpiralOptions,
 }
 
 fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
@@ -16,24 +31,111 @@ fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
 }
 
 impl Spiral {
-    pub async fn handle(event: Box<WindowEvent>) {
-        if let Ok(mut manager) = Self::new().await {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        options: SpiralOptions,
+        mode: RunMode,
+    ) {
+        if let Ok(mut manager) = Self::new(tree, options, mode).await {
             manager.handle(event).await;
         }
     }
 
-    pub async fn new() -> Result<Self> {
-        let connection = Connection::new().await?;
-        Ok(Self { connection })
+    pub async fn new(tree: Arc<TreeCache>, options: SpiralOptions, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            options,
+        })
+    }
+
+    /// Gives a new window its share of the split it ended up in, the window
+    /// that was split keeps the rest.
+    async fn resize_split(&mut self, event: WindowEvent) -> Result<()> {
+        let ratio = match self.options.ratio {
+            Some(ratio) => ratio,
+            None => return Ok(()),
+        };
+        let tree = &self.tree;
+        let node = if let Some(node) = tree.get(event.container.id) {
+            node
+        } else {
+            log::warn!(
+                "spiral layout, no node found with id {}",
+                event.container.id
+            );
+            return Ok(());
+        };
+        let ws = node.get_workspace(tree)?;
+        if should_skip_layout_of_workspace(&ws) || !node.is_window() || node.is_full_screen() {
+            return Ok(());
+        }
+        let parent = node.get_parent(tree)?;
+        if parent.nodes.len() != 2 || parent.nodes[1].id != node.id {
+            return Ok(());
+        }
+        let dimension = match parent.layout {
+            NodeLayout::SplitH => "width",
+            NodeLayout::SplitV => "height",
+            _ => return Ok(()),
+        };
+        let cmd = format!(
+            "[con_id={}] resize set {} {}",
+            node.id,
+            dimension,
+            100 - ratio
+        );
+        log::debug!("spiral layout: {}", cmd);
+        self.commands.run_command(cmd).await
+    }
+
+    /// The number of containers between the node and its workspace.
+    fn depth(&self, node: &Node) -> usize {
+        let mut depth = 0;
+        let mut current = self.tree.parent_of(node.id);
+        while let Some(parent) = current {
+            if !parent.is_container() {
+                break;
+            }
+            depth += 1;
+            current = self.tree.parent_of(parent.id);
+        }
+        depth
+    }
+
+    fn split_direction(&self, node: &Node) -> Result<&'static str> {
+        let split = match self.options.direction {
+            Some(SplitDirection::Horizontal) => "h",
+            Some(SplitDirection::Vertical) => "v",
+            Some(SplitDirection::Alternate) => {
+                let parent = node.get_parent(&self.tree)?;
+                if parent.nodes.len() > 1 && parent.layout == NodeLayout::SplitH {
+                    "v"
+                } else {
+                    "h"
+                }
+            }
+            None if node.rect.height > node.rect.width => "v",
+            None => "h",
+        };
+        Ok(split)
     }
 
     async fn layout(&mut self, event: WindowEvent) -> Result<()> {
         log::debug!("spiral manager handling event: {:?}", event.change);
-        let tree = self.connection.get_tree().await?;
-        let node = tree
-            .find_as_ref(|n| n.id == event.container.id)
-            .unwrap_or_else(|| panic!("no node found with id {}", event.container.id));
-        let ws = node.get_workspace().await?;
+        let tree = &self.tree;
+        let node = if let Some(node) = tree.get(event.container.id) {
+            node
+        } else {
+            log::warn!(
+                "spiral layout, no node found with id {}",
+                event.container.id
+            );
+            return Ok(());
+        };
+        let ws = node.get_workspace(tree)?;
         if should_skip_layout_of_workspace(&ws) {
             log::debug!("skip spiral layout of \"special\" workspace");
             return Ok(());
@@ -41,16 +143,31 @@ impl Spiral {
         if !(node.is_floating_window()
             || node.is_floating_container()
             || node.is_full_screen()
-            || node.is_stacked().await?
-            || node.is_tabbed().await?)
+            || node.is_stacked(tree)?
+            || node.is_tabbed(tree)?)
         {
-            let cmd = if node.rect.height > node.rect.width {
-                format!("[con_id={}] focus; split v", node.id)
-            } else {
-                format!("[con_id={}] focus; split h", node.id)
+            let depth = self.depth(node);
+            let cmd = match self.options.max_depth {
+                Some(max_depth) if depth >= max_depth as usize => {
+                    log::debug!("spiral layout, max depth {} reached", max_depth);
+                    match self.options.overflow_layout {
+                        // tiled is rejected when the options are parsed
+                        StackLayout::Tabbed | StackLayout::Tiled => {
+                            format!("[con_id={}] focus; split v; layout tabbed", node.id)
+                        }
+                        StackLayout::Stacked => {
+                            format!("[con_id={}] focus; split v; layout stacking", node.id)
+                        }
+                    }
+                }
+                _ => format!(
+                    "[con_id={}] focus; split {}",
+                    node.id,
+                    self.split_direction(node)?
+                ),
             };
             log::debug!("spiral layout: {}", cmd);
-            self.connection.run_command(cmd).await?;
+            self.commands.run_command(cmd).await?;
         };
 
         Ok(())
@@ -64,6 +181,11 @@ impl WindowEventHandler for Spiral {
                     log::error!("spiral manager, layout err: {}", e);
                 };
             }
+            WindowChange::New => {
+                if let Err(e) = self.resize_split(*event).await {
+                    log::error!("spiral manager, resize err: {}", e);
+                };
+            }
             _ => log::debug!("spiral manager, not handling event: {:?}", event.change),
         }
     }
@@ -1,11 +1,14 @@
 use crate::{
     layout::StackLayout,
     node_ext::NodeExt,
-    utils::{get_focused_workspace, is_persway_tmp_workspace, is_scratchpad_workspace},
+    server::command_runner::{CommandRunner, RunMode},
+    tree_cache::TreeCache,
+    utils::{is_persway_tmp_workspace, is_scratchpad_workspace},
 };
 
-use anyhow::Result;
-use swayipc_async::{Connection, WindowChange, WindowEvent, Workspace};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use swayipc_async::{WindowChange, WindowEvent, Workspace};
 
 use super::super::traits::WindowEventHandler;
 
@@ -14,33 +17,53 @@ fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
 }
 
 pub struct StackMain {
-    connection: Connection,
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
     size: u8,
     stack_layout: StackLayout,
+    pushed: bool,
 }
 
 impl StackMain {
-    pub async fn handle(event: Box<WindowEvent>, size: u8, stack_layout: StackLayout) {
-        if let Ok(mut manager) = Self::new(size, stack_layout).await {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        size: u8,
+        stack_layout: StackLayout,
+        pushed: bool,
+        mode: RunMode,
+    ) {
+        if let Ok(mut manager) = Self::new(tree, size, stack_layout, pushed, mode).await {
             manager.handle(event).await;
         }
     }
 
-    pub async fn new(size: u8, stack_layout: StackLayout) -> Result<Self> {
-        let connection = Connection::new().await?;
+    pub async fn new(
+        tree: Arc<TreeCache>,
+        size: u8,
+        stack_layout: StackLayout,
+        pushed: bool,
+        mode: RunMode,
+    ) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
         Ok(Self {
-            connection,
+            commands,
+            tree,
             size,
             stack_layout,
+            pushed,
         })
     }
 
     async fn on_new_window(&mut self, event: &WindowEvent) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
-        let node = tree
-            .find_as_ref(|n| n.id == event.container.id)
-            .unwrap_or_else(|| panic!("no node found with id {}", event.container.id));
-        let ws = node.get_workspace().await?;
+        let tree = Arc::clone(&self.tree);
+        let node = if let Some(node) = tree.get(event.container.id) {
+            node
+        } else {
+            log::warn!("no node found with id {}", event.container.id);
+            return Ok(());
+        };
+        let ws = node.get_workspace(&tree)?;
         if should_skip_layout_of_workspace(&ws) {
             log::debug!("skip stack_main layout of \"special\" workspace");
             return Ok(());
@@ -51,7 +74,7 @@ impl StackMain {
             return Ok(());
         }
 
-        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
         log::debug!("new_window id: {}", event.container.id);
         log::debug!("workspace nodes len: {}", wstree.nodes.len());
         let layout = match self.stack_layout {
@@ -60,14 +83,26 @@ impl StackMain {
             StackLayout::Tiled => "split v",
         };
         match wstree.nodes.len() {
+            1 if self.pushed && !wstree.nodes[0].is_window() => {
+                // only the stack is left after stack-push, a new window takes
+                // over the empty main area, the push's own moves are left alone
+                if event.change == WindowChange::New {
+                    let cmd = format!(
+                        "[con_id={}] focus; move right; resize set width {}",
+                        event.container.id, self.size
+                    );
+                    self.commands.run_command(cmd).await?;
+                }
+                Ok(())
+            }
             1 => {
                 let cmd = format!("[con_id={}] focus; split h", event.container.id);
-                self.connection.run_command(cmd).await?;
+                self.commands.run_command(cmd).await?;
                 Ok(())
             }
             2 => {
-                let main = wstree.nodes.last().expect("main window not found");
-                let stack = wstree.nodes.first().expect("stack container not found");
+                let main = wstree.nodes.last().context("main window not found")?;
+                let stack = wstree.nodes.first().context("stack container not found")?;
 
                 let cmd = if stack.is_window() {
                     format!(
@@ -86,7 +121,7 @@ impl StackMain {
                     String::from("nop event container not in stack")
                 };
 
-                self.connection.run_command(cmd).await?;
+                self.commands.run_command(cmd).await?;
                 Ok(())
             }
             3 => {
@@ -95,8 +130,8 @@ impl StackMain {
                     .iter()
                     .skip(1)
                     .find(|n| n.is_window() && n.id != event.container.id)
-                    .expect("main window not found");
-                let stack = wstree.nodes.first().expect("stack container not found");
+                    .context("main window not found")?;
+                let stack = wstree.nodes.first().context("stack container not found")?;
                 let stack_mark = format!("_stack_{}", stack.id);
 
                 let cmd = format!(
@@ -109,31 +144,28 @@ impl StackMain {
 
                 log::debug!("new_window: {}", cmd);
 
-                self.connection.run_command(cmd).await?;
+                self.commands.run_command(cmd).await?;
                 Ok(())
             }
             _ => Ok(()),
         }
     }
     async fn on_close_window(&mut self, event: &WindowEvent) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
-        let ws = get_focused_workspace(&mut self.connection).await?;
-        if should_skip_layout_of_workspace(&ws) {
+        let tree = Arc::clone(&self.tree);
+        let ws = tree.focused_workspace().context("no focused workspace")?;
+        if should_skip_layout_of_workspace(ws) {
             log::debug!("skip stack_main layout of \"special\" workspace");
             return Ok(());
         }
 
-        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+        let wstree = tree.get(ws.id).context("workspace not found in tree")?;
 
         if wstree.nodes.len() == 1 {
             if let Some(stack) = wstree.nodes.iter().find(|n| n.id != event.container.id) {
                 let stack_current = stack
                     .find_as_ref(|n| n.is_window() && n.focused)
-                    .unwrap_or_else(|| {
-                        stack
-                            .find_as_ref(|n| n.visible.unwrap_or(false))
-                            .expect("stack should have a visible node")
-                    });
+                    .or_else(|| stack.find_as_ref(|n| n.visible.unwrap_or(false)))
+                    .context("stack should have a visible node")?;
 
                 let cmd = if wstree.iter().filter(|n| n.is_window()).count() == 1 {
                     log::debug!("on_close_window, count 1, stack_id: {}", stack_current.id);
@@ -152,22 +184,22 @@ impl StackMain {
                     )
                 };
                 log::debug!("close_window: {}", cmd);
-                self.connection.run_command(cmd).await?;
+                self.commands.run_command(cmd).await?;
             }
         }
         Ok(())
     }
     async fn on_move_window(&mut self, event: &WindowEvent) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
+        let tree = Arc::clone(&self.tree);
 
-        let node = if let Some(node) = tree.find_as_ref(|n| n.id == event.container.id) {
+        let node = if let Some(node) = tree.get(event.container.id) {
             node
         } else {
             log::warn!("no node found with id {}", event.container.id);
             return Ok(());
         };
 
-        let ws = if let Ok(ws) = node.get_workspace().await {
+        let ws = if let Ok(ws) = node.get_workspace(&tree) {
             ws
         } else {
             log::warn!("node had no workspace");
@@ -184,7 +216,7 @@ impl StackMain {
             return Ok(());
         }
 
-        let focused_ws = get_focused_workspace(&mut self.connection).await?;
+        let focused_ws = tree.focused_workspace().context("no focused workspace")?;
 
         if ws.id == focused_ws.id {
             log::debug!("move_window within workspace: {}", ws.num);
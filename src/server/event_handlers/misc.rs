@@ -1,2 +1,6 @@
+pub mod dimmer;
+pub mod exec_hooks;
+pub mod focus_hooks;
 pub mod window_focus;
+pub mod window_hooks;
 pub mod workspace_renamer;
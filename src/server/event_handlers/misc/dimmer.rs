@@ -0,0 +1,273 @@
+use super::super::traits::WindowEventHandler;
+use crate::{
+    criteria::Criteria,
+    node_ext::NodeExt,
+    server::command_runner::{CommandRunner, RunMode},
+    tree_cache::TreeCache,
+};
+
+use anyhow::Result;
+use async_std::task;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use swayipc_async::{Node, WindowChange, WindowEvent};
+
+/// How long each step of a fade is shown, about one frame at 60Hz.
+const FRAME: Duration = Duration::from_millis(16);
+
+/// The opacities of focused and other windows, see the --dim-* options.
+#[derive(Clone, Debug)]
+pub struct DimOptions {
+    pub active: f32,
+    pub inactive: f32,
+    pub fade: Duration,
+    pub exclude: Vec<Criteria>,
+}
+
+/// The opacity persway last gave each window, and which fade of a window is the
+/// current one so that an older fade stops when a newer one starts.
+#[derive(Debug, Default)]
+pub struct DimState {
+    initialized: bool,
+    opacity: HashMap<i64, f32>,
+    fades: HashMap<i64, u64>,
+}
+
+/// The one connection all dimming commands go through, opened on first use,
+/// so that fades don't each open their own and their steps reach sway in order.
+#[derive(Clone, Default)]
+pub struct DimRunner(Arc<async_std::sync::Mutex<Option<CommandRunner>>>);
+
+impl fmt::Debug for DimRunner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DimRunner")
+    }
+}
+
+impl DimRunner {
+    /// Runs the command if it is still wanted, which is checked while holding
+    /// the connection so that a newer fade can't send a step in between.
+    /// Returns whether it ran.
+    async fn run_if(
+        &self,
+        mode: &RunMode,
+        cmd: &str,
+        wanted: impl FnOnce() -> bool,
+    ) -> Result<bool> {
+        let mut runner = self.0.lock().await;
+        if !wanted() {
+            return Ok(false);
+        }
+        let commands = match runner.as_mut() {
+            Some(commands) => commands,
+            None => runner.insert(CommandRunner::new(mode.clone()).await?),
+        };
+        if let Err(e) = commands.run_command(cmd).await {
+            // the connection is what failed, open a new one next time
+            *runner = None;
+            return Err(e);
+        }
+        Ok(true)
+    }
+}
+
+pub struct Dimmer {
+    runner: DimRunner,
+    tree: Arc<TreeCache>,
+    options: DimOptions,
+    state: Arc<Mutex<DimState>>,
+    previously_focused_id: Option<i64>,
+    mode: RunMode,
+}
+
+impl Dimmer {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        options: DimOptions,
+        state: Arc<Mutex<DimState>>,
+        runner: DimRunner,
+        previously_focused_id: Option<i64>,
+        mode: RunMode,
+    ) {
+        let mut manager = Self::new(tree, options, state, runner, previously_focused_id, mode);
+        manager.handle(event).await;
+    }
+
+    pub fn new(
+        tree: Arc<TreeCache>,
+        options: DimOptions,
+        state: Arc<Mutex<DimState>>,
+        runner: DimRunner,
+        previously_focused_id: Option<i64>,
+        mode: RunMode,
+    ) -> Self {
+        Self {
+            runner,
+            tree,
+            options,
+            state,
+            previously_focused_id,
+            mode,
+        }
+    }
+
+    fn excluded(&self, window: &Node) -> bool {
+        self.options
+            .exclude
+            .iter()
+            .any(|criteria| criteria.matches(&self.tree, window))
+    }
+
+    /// Dims every window but the focused one once, later focus changes only
+    /// touch the windows gaining or losing focus.
+    async fn dim_all(&mut self, focused: i64) -> Result<()> {
+        let mut cmd = String::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.initialized {
+                return Ok(());
+            }
+            state.initialized = true;
+            let windows = self
+                .tree
+                .workspaces()
+                .iter()
+                .filter_map(|ws| self.tree.get(ws.id))
+                .flat_map(|ws| ws.iter().filter(|n| n.is_any_window()));
+            for window in windows {
+                if window.id == focused || self.excluded(window) {
+                    continue;
+                }
+                state.opacity.insert(window.id, self.options.inactive);
+                cmd.push_str(&format!(
+                    "[con_id={}] opacity {:.2}; ",
+                    window.id, self.options.inactive
+                ));
+            }
+        }
+        if !cmd.is_empty() {
+            log::debug!("dimmer: {}", cmd);
+            self.runner
+                .run_if(&self.mode, cmd.trim_end_matches("; "), || true)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Starts fading the window to the given opacity from the one it has now,
+    /// cancelling a fade of the window that is still going.
+    fn fade(&self, id: i64, to: f32) {
+        let (from, fade) = {
+            let mut state = self.state.lock().unwrap();
+            // sway opens windows fully opaque
+            let from = state.opacity.get(&id).copied().unwrap_or(1.0);
+            // a fade stops at its next step once it is no longer the current one
+            let fade = state.fades.entry(id).or_default();
+            *fade += 1;
+            (from, *fade)
+        };
+        if from == to {
+            return;
+        }
+        let steps = (self.options.fade.as_millis() / FRAME.as_millis()).max(1) as u32;
+        let state = Arc::clone(&self.state);
+        let runner = self.runner.clone();
+        let mode = self.mode.clone();
+        let run = async move {
+            for step in 1..=steps {
+                let opacity = if step == steps {
+                    to
+                } else {
+                    from + (to - from) * step as f32 / steps as f32
+                };
+                let current = || {
+                    let mut state = state.lock().unwrap();
+                    let current = state.fades.get(&id) == Some(&fade);
+                    if current {
+                        state.opacity.insert(id, opacity);
+                    }
+                    current
+                };
+                let cmd = format!("[con_id={}] opacity {:.2}", id, opacity);
+                if !runner.run_if(&mode, &cmd, current).await? {
+                    return Ok(());
+                }
+                if step < steps {
+                    task::sleep(FRAME).await;
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        };
+        task::spawn(async move {
+            if let Err(e) = run.await {
+                log::error!("dimmer, fade err: {}", e);
+            }
+        });
+    }
+
+    async fn on_window_focus(&mut self, event: &WindowEvent) -> Result<()> {
+        let id = event.container.id;
+        self.dim_all(id).await?;
+        let tree = Arc::clone(&self.tree);
+        if let Some(previous) = self.previously_focused_id.and_then(|id| tree.get(id)) {
+            if !self.excluded(previous) {
+                self.fade(previous.id, self.options.inactive);
+            }
+        }
+        if !self.excluded(&event.container) {
+            self.fade(id, self.options.active);
+        }
+        Ok(())
+    }
+
+    /// Dims a window opened without focus, windows opened before the first
+    /// focus change are left to dim_all.
+    async fn on_new_window(&mut self, event: &WindowEvent) -> Result<()> {
+        let id = event.container.id;
+        let focused = self.tree.get(id).is_some_and(|n| n.focused);
+        if focused || self.excluded(&event.container) {
+            return Ok(());
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            if !state.initialized {
+                return Ok(());
+            }
+            state.opacity.insert(id, self.options.inactive);
+        }
+        let cmd = format!("[con_id={}] opacity {:.2}", id, self.options.inactive);
+        log::debug!("dimmer: {}", cmd);
+        self.runner.run_if(&self.mode, &cmd, || true).await?;
+        Ok(())
+    }
+
+    fn on_window_close(&mut self, event: &WindowEvent) {
+        let mut state = self.state.lock().unwrap();
+        state.opacity.remove(&event.container.id);
+        state.fades.remove(&event.container.id);
+    }
+}
+
+impl WindowEventHandler for Dimmer {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        match event.change {
+            WindowChange::Focus => {
+                if let Err(e) = self.on_window_focus(&event).await {
+                    log::error!("dimmer, err: {}", e);
+                }
+            }
+            WindowChange::New => {
+                if let Err(e) = self.on_new_window(&event).await {
+                    log::error!("dimmer, err: {}", e);
+                }
+            }
+            WindowChange::Close => self.on_window_close(&event),
+            _ => log::debug!("dimmer, not handling event: {:?}", event.change),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::super::traits::WindowEventHandler;
+use super::workspace_renamer::get_app_name;
+use crate::{server::command_runner::RunMode, tree_cache::TreeCache};
+
+use anyhow::{Context, Result};
+use async_std::task;
+use serde::Serialize;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::Arc,
+};
+use swayipc_async::{WindowChange, WindowEvent};
+
+/// Shell programs run on window events, see the --exec-on-* options.
+#[derive(Clone, Debug, Default)]
+pub struct ExecPrograms {
+    pub focus: Vec<String>,
+    pub new: Vec<String>,
+    pub close: Vec<String>,
+    pub urgent: Vec<String>,
+}
+
+impl ExecPrograms {
+    pub fn for_event(&self, event: &WindowEvent) -> &[String] {
+        match event.change {
+            WindowChange::Focus => &self.focus,
+            WindowChange::New => &self.new,
+            WindowChange::Close => &self.close,
+            WindowChange::Urgent if event.container.urgent => &self.urgent,
+            _ => &[],
+        }
+    }
+}
+
+/// What the programs get to know about the window, as environment variables
+/// and as JSON on stdin.
+#[derive(Serialize)]
+struct WindowContext {
+    event: String,
+    id: i64,
+    app_id: String,
+    app_name: String,
+    title: String,
+    workspace: String,
+    layout: String,
+}
+
+impl WindowContext {
+    fn env(&self) -> [(&'static str, String); 7] {
+        [
+            ("PERSWAY_EVENT", self.event.clone()),
+            ("PERSWAY_WINDOW_ID", self.id.to_string()),
+            ("PERSWAY_APP_ID", self.app_id.clone()),
+            ("PERSWAY_APP_NAME", self.app_name.clone()),
+            ("PERSWAY_TITLE", self.title.clone()),
+            ("PERSWAY_WORKSPACE", self.workspace.clone()),
+            ("PERSWAY_LAYOUT", self.layout.clone()),
+        ]
+    }
+}
+
+pub struct ExecHooks {
+    tree: Arc<TreeCache>,
+    programs: Vec<String>,
+    layout: String,
+    mode: RunMode,
+}
+
+impl ExecHooks {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        programs: Vec<String>,
+        layout: String,
+        mode: RunMode,
+    ) {
+        let mut manager = Self::new(tree, programs, layout, mode);
+        manager.handle(event).await;
+    }
+
+    pub fn new(tree: Arc<TreeCache>, programs: Vec<String>, layout: String, mode: RunMode) -> Self {
+        Self {
+            tree,
+            programs,
+            layout,
+            mode,
+        }
+    }
+
+    fn context(&self, event: &WindowEvent) -> WindowContext {
+        let change = serde_json::to_value(event.change).unwrap_or_default();
+        WindowContext {
+            event: change.as_str().unwrap_or_default().to_string(),
+            id: event.container.id,
+            app_id: event.container.app_id.clone().unwrap_or_default(),
+            app_name: get_app_name(&event.container).unwrap_or_default(),
+            title: event.container.name.clone().unwrap_or_default(),
+            workspace: self
+                .tree
+                .workspace_of(event.container.id)
+                .map(|ws| ws.name.clone())
+                .unwrap_or_default(),
+            layout: self.layout.clone(),
+        }
+    }
+
+    /// Spawns the programs through the shell without waiting for them, their
+    /// exit is only logged.
+    fn exec(&self, event: &WindowEvent) -> Result<()> {
+        let context = self.context(event);
+        let stdin = serde_json::to_string(&context)?;
+        for program in &self.programs {
+            if !matches!(self.mode, RunMode::Execute) {
+                log::info!("exec hooks, would run: {}", program);
+                continue;
+            }
+            log::debug!("exec hooks, running: {}", program);
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg(program)
+                .envs(context.env())
+                .stdin(Stdio::piped())
+                .spawn()
+                .with_context(|| format!("couldn't run {}", program))?;
+            let program = program.clone();
+            let stdin = stdin.clone();
+            task::spawn_blocking(move || {
+                if let Some(mut pipe) = child.stdin.take() {
+                    // programs that don't read their stdin close it early
+                    let _ = pipe.write_all(stdin.as_bytes());
+                }
+                match child.wait() {
+                    Ok(status) if !status.success() => {
+                        log::warn!("exec hooks, {} exited with {}", program, status)
+                    }
+                    Err(e) => log::error!("exec hooks, waiting for {}: {}", program, e),
+                    _ => {}
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl WindowEventHandler for ExecHooks {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        if let Err(e) = self.exec(&event) {
+            log::error!("exec hooks, err: {}", e);
+        }
+    }
+}
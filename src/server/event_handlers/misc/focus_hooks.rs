@@ -0,0 +1,93 @@
+use super::super::traits::WindowEventHandler;
+use crate::{
+    criteria::Hook,
+    server::command_runner::{CommandRunner, RunMode},
+    tree_cache::TreeCache,
+};
+
+use anyhow::Result;
+use std::sync::Arc;
+use swayipc_async::{Node, WindowChange, WindowEvent};
+
+pub struct FocusHooks {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    focus_hooks: Vec<Hook>,
+    focus_leave_hooks: Vec<Hook>,
+    previously_focused_id: Option<i64>,
+}
+
+impl FocusHooks {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        focus_hooks: Vec<Hook>,
+        focus_leave_hooks: Vec<Hook>,
+        previously_focused_id: Option<i64>,
+        mode: RunMode,
+    ) {
+        if let Ok(mut manager) = Self::new(
+            tree,
+            focus_hooks,
+            focus_leave_hooks,
+            previously_focused_id,
+            mode,
+        )
+        .await
+        {
+            manager.handle(event).await;
+        }
+    }
+
+    pub async fn new(
+        tree: Arc<TreeCache>,
+        focus_hooks: Vec<Hook>,
+        focus_leave_hooks: Vec<Hook>,
+        previously_focused_id: Option<i64>,
+        mode: RunMode,
+    ) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            focus_hooks,
+            focus_leave_hooks,
+            previously_focused_id,
+        })
+    }
+
+    /// Runs the commands of the hooks whose criteria the window matches.
+    async fn run_hooks(&mut self, hooks: &[Hook], window: &Node) -> Result<()> {
+        for hook in hooks {
+            if hook.criteria.matches(&self.tree, window) {
+                let cmd = hook.command_for(window.id);
+                log::debug!("focus hooks: {}", cmd);
+                self.commands.run_command(cmd).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_window_focus(&mut self, event: &WindowEvent) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        if let Some(previous) = self.previously_focused_id.and_then(|id| tree.get(id)) {
+            let hooks = std::mem::take(&mut self.focus_leave_hooks);
+            self.run_hooks(&hooks, previous).await?;
+        }
+        let hooks = std::mem::take(&mut self.focus_hooks);
+        self.run_hooks(&hooks, &event.container).await
+    }
+}
+
+impl WindowEventHandler for FocusHooks {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        match event.change {
+            WindowChange::Focus => {
+                if let Err(e) = self.on_window_focus(&event).await {
+                    log::error!("focus hooks, err: {}", e);
+                }
+            }
+            _ => log::debug!("focus hooks, not handling event: {:?}", event.change),
+        }
+    }
+}
@@ -1,10 +1,11 @@
 use super::super::traits::WindowEventHandler;
+use crate::server::command_runner::{CommandRunner, RunMode};
 
 use anyhow::Result;
-use swayipc_async::{Connection, WindowChange, WindowEvent};
+use swayipc_async::{WindowChange, WindowEvent};
 
 pub struct WindowFocus {
-    connection: Connection,
+    commands: CommandRunner,
     window_focus_cmd: Option<String>,
     window_focus_leave_cmd: Option<String>,
     previously_focused_id: Option<i64>,
@@ -15,8 +16,17 @@ impl WindowFocus {
         event: Box<WindowEvent>,
         window_focus_cmd: Option<String>,
         window_focus_leave_cmd: Option<String>,
+        previously_focused_id: Option<i64>,
+        mode: RunMode,
     ) {
-        if let Ok(mut manager) = Self::new(window_focus_cmd, window_focus_leave_cmd).await {
+        if let Ok(mut manager) = Self::new(
+            window_focus_cmd,
+            window_focus_leave_cmd,
+            previously_focused_id,
+            mode,
+        )
+        .await
+        {
             manager.handle(event).await;
         }
     }
@@ -24,19 +34,21 @@ impl WindowFocus {
     pub async fn new(
         window_focus_cmd: Option<String>,
         window_focus_leave_cmd: Option<String>,
+        previously_focused_id: Option<i64>,
+        mode: RunMode,
     ) -> Result<Self> {
-        let connection = Connection::new().await?;
+        let commands = CommandRunner::new(mode).await?;
         Ok(Self {
-            connection,
+            commands,
             window_focus_cmd,
             window_focus_leave_cmd,
-            previously_focused_id: None,
+            previously_focused_id,
         })
     }
 
     async fn on_window_focus(&mut self) -> Result<()> {
         if let Some(window_focus_cmd) = &self.window_focus_cmd {
-            self.connection.run_command(window_focus_cmd).await?;
+            self.commands.run_command(window_focus_cmd).await?;
         }
         Ok(())
     }
@@ -44,7 +56,7 @@ impl WindowFocus {
     async fn on_window_focus_leave(&mut self) -> Result<()> {
         if let Some(window_focus_leave_cmd) = &self.window_focus_leave_cmd {
             if let Some(id) = self.previously_focused_id {
-                self.connection
+                self.commands
                     .run_command(format!("[con_id={id}] {}", window_focus_leave_cmd))
                     .await?;
             }
@@ -0,0 +1,97 @@
+use super::super::traits::WindowEventHandler;
+use crate::{
+    criteria::Hook,
+    server::command_runner::{CommandRunner, RunMode},
+    tree_cache::TreeCache,
+};
+
+use anyhow::Result;
+use std::sync::Arc;
+use swayipc_async::{WindowChange, WindowEvent};
+
+/// Sway command hooks run on window events, see the --on-* options.
+#[derive(Clone, Debug, Default)]
+pub struct WindowHookSet {
+    pub new: Vec<Hook>,
+    pub close: Vec<Hook>,
+    pub fullscreen: Vec<Hook>,
+    pub floating: Vec<Hook>,
+    pub urgent: Vec<Hook>,
+    pub title: Vec<Hook>,
+    pub mark: Vec<Hook>,
+}
+
+impl WindowHookSet {
+    pub fn for_event(&self, event: &WindowEvent) -> &[Hook] {
+        match event.change {
+            WindowChange::New => &self.new,
+            WindowChange::Close => &self.close,
+            WindowChange::FullscreenMode => &self.fullscreen,
+            WindowChange::Floating => &self.floating,
+            WindowChange::Urgent if event.container.urgent => &self.urgent,
+            WindowChange::Title => &self.title,
+            WindowChange::Mark => &self.mark,
+            _ => &[],
+        }
+    }
+}
+
+pub struct WindowHooks {
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    hooks: Vec<Hook>,
+}
+
+impl WindowHooks {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        hooks: Vec<Hook>,
+        mode: RunMode,
+    ) {
+        if let Ok(mut manager) = Self::new(tree, hooks, mode).await {
+            manager.handle(event).await;
+        }
+    }
+
+    pub async fn new(tree: Arc<TreeCache>, hooks: Vec<Hook>, mode: RunMode) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            hooks,
+        })
+    }
+
+    /// Runs the commands of the hooks whose criteria the window matches.
+    async fn run_hooks(&mut self, event: &WindowEvent) -> Result<()> {
+        // a closed window is gone from the tree, it can only be matched on its
+        // own and a command targeting it would match nothing
+        let closed = event.change == WindowChange::Close;
+        for hook in &self.hooks {
+            let matches = if closed {
+                hook.criteria.matches_window(&event.container)
+            } else {
+                hook.criteria.matches(&self.tree, &event.container)
+            };
+            if matches {
+                let cmd = if closed {
+                    hook.command.clone()
+                } else {
+                    hook.command_for(event.container.id)
+                };
+                log::debug!("window hooks, {:?}: {}", event.change, cmd);
+                self.commands.run_command(cmd).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WindowEventHandler for WindowHooks {
+    async fn handle(&mut self, event: Box<WindowEvent>) {
+        if let Err(e) = self.run_hooks(&event).await {
+            log::error!("window hooks, err: {}", e);
+        }
+    }
+}
@@ -1,26 +1,145 @@
 use super::super::traits::WindowEventHandler;
-use crate::utils;
+use crate::{
+    commands::RenameApps,
+    node_ext::NodeExt,
+    server::command_runner::{CommandRunner, RunMode},
+    tree_cache::TreeCache,
+    utils,
+};
 
 use anyhow::Result;
-use swayipc_async::{Connection, WindowChange, WindowEvent, Workspace};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use swayipc_async::{Node, WindowChange, WindowEvent, Workspace};
+
+/// How workspaces are named, see the --rename-format, --app-* and --fallback-icon options.
+/// The names and icons are keyed by lowercased app_id or class.
+#[derive(Clone, Debug)]
+pub struct RenameOptions {
+    pub format: String,
+    pub apps: RenameApps,
+    pub names: HashMap<String, String>,
+    pub icons: HashMap<String, String>,
+    pub fallback_icon: Option<String>,
+    pub max_length: Option<usize>,
+}
+
+impl RenameOptions {
+    fn name_of(&self, app: &str) -> String {
+        self.names
+            .get(app)
+            .cloned()
+            .unwrap_or_else(|| app.to_string())
+    }
+
+    fn icon_of(&self, app: &str) -> String {
+        self.icons
+            .get(app)
+            .or(self.fallback_icon.as_ref())
+            .cloned()
+            .unwrap_or_else(|| self.name_of(app))
+    }
+
+    /// Fills in the template for a workspace named after the given apps and how
+    /// many windows each has, cutting it short at the max length. The template
+    /// is scanned once so placeholders within the names filled in stay as they are.
+    pub fn render(
+        &self,
+        num: &str,
+        focused: &str,
+        apps: &[(String, usize)],
+        count: usize,
+    ) -> String {
+        let with_count = |shown: String, windows: usize| match windows {
+            1 => shown,
+            _ => format!("{}×{}", shown, windows),
+        };
+        let names: Vec<String> = apps
+            .iter()
+            .map(|(app, windows)| with_count(self.name_of(app), *windows))
+            .collect();
+        let icons: Vec<String> = apps
+            .iter()
+            .map(|(app, windows)| with_count(self.icon_of(app), *windows))
+            .collect();
+        let value_of = |placeholder: &str| match placeholder {
+            "num" => Some(num.to_string()),
+            "focused_app" => Some(self.name_of(focused)),
+            "focused_icon" => Some(self.icon_of(focused)),
+            "apps" => Some(names.join(", ")),
+            "icons" => Some(icons.join(" ")),
+            "count" => Some(count.to_string()),
+            _ => None,
+        };
+        let mut name = String::new();
+        let mut rest = self.format.as_str();
+        while let Some(start) = rest.find('{') {
+            name.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let filled = rest
+                .find('}')
+                .and_then(|end| Some((value_of(&rest[1..end])?, end)));
+            match filled {
+                Some((value, end)) => {
+                    name.push_str(&value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    name.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        name.push_str(rest);
+        match self.max_length {
+            Some(max) if name.chars().count() > max => {
+                let mut name: String = name.chars().take(max - 1).collect();
+                name.push('…');
+                name
+            }
+            _ => name,
+        }
+    }
+}
+
+/// The names workspaces without a number had before persway renamed them,
+/// keyed by workspace id, they take the place of the number in the template.
+pub type BaseNames = Arc<Mutex<HashMap<i64, String>>>;
 
 pub struct WorkspaceRenamer {
-    connection: Connection,
+    commands: CommandRunner,
+    tree: Arc<TreeCache>,
+    options: RenameOptions,
+    mru: Vec<i64>,
+    base_names: BaseNames,
 }
 
 fn should_skip_rename_of_workspace(workspace: &Workspace) -> bool {
     utils::is_persway_tmp_workspace(workspace) || utils::is_scratchpad_workspace(workspace)
 }
 
-fn get_app_name(event: &WindowEvent) -> Option<String> {
-    let app_id =
-        event
-            .container
-            .app_id
-            .as_ref()
-            .and_then(|id| if id.is_empty() { None } else { Some(id) });
+/// The window of the workspace that has, or last had, focus within it.
+fn focused_window(ws: &Node) -> Option<&Node> {
+    let mut node = ws;
+    while let Some(id) = node.focus.first() {
+        node = node
+            .nodes
+            .iter()
+            .chain(node.floating_nodes.iter())
+            .find(|n| n.id == *id)?;
+    }
+    node.is_any_window().then_some(node)
+}
 
-    let name = event.container.name.as_ref().and_then(|name| {
+pub fn get_app_name(node: &Node) -> Option<String> {
+    let app_id = node
+        .app_id
+        .as_ref()
+        .and_then(|id| if id.is_empty() { None } else { Some(id) });
+
+    let name = node.name.as_ref().and_then(|name| {
         if name.is_empty() {
             None
         } else {
@@ -28,7 +147,7 @@ fn get_app_name(event: &WindowEvent) -> Option<String> {
         }
     });
 
-    let class = event.container.window_properties.as_ref().and_then(|p| {
+    let class = node.window_properties.as_ref().and_then(|p| {
         p.class
             .as_ref()
             .and_then(|class| if class.is_empty() { None } else { Some(class) })
@@ -45,36 +164,142 @@ fn get_app_name(event: &WindowEvent) -> Option<String> {
 }
 
 impl WorkspaceRenamer {
-    pub async fn handle(event: Box<WindowEvent>) {
-        if let Ok(mut manager) = Self::new().await {
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Arc<TreeCache>,
+        options: RenameOptions,
+        mru: Vec<i64>,
+        base_names: BaseNames,
+        mode: RunMode,
+    ) {
+        if let Ok(mut manager) = Self::new(tree, options, mru, base_names, mode).await {
             manager.handle(event).await;
         }
     }
 
-    pub async fn new() -> Result<Self> {
-        let connection = Connection::new().await?;
-        Ok(Self { connection })
+    pub async fn new(
+        tree: Arc<TreeCache>,
+        options: RenameOptions,
+        mru: Vec<i64>,
+        base_names: BaseNames,
+        mode: RunMode,
+    ) -> Result<Self> {
+        let commands = CommandRunner::for_tree(mode, &tree).await?;
+        Ok(Self {
+            commands,
+            tree,
+            options,
+            mru,
+            base_names,
+        })
     }
 
-    async fn rename_workspace(&mut self, event: WindowEvent) -> Result<()> {
-        log::debug!("workspace name manager handling event: {:?}", event.change);
-        let focused_ws = utils::get_focused_workspace(&mut self.connection).await?;
-        if should_skip_rename_of_workspace(&focused_ws) {
-            log::debug!("workspace name manager skip renaming workspace");
-            return Ok(());
+    /// The windows the workspace is named after, in the order the options ask for.
+    fn named_after<'a>(&self, ws: &'a Node, focused: Option<&'a Node>) -> Vec<&'a Node> {
+        // the iterator walks the tree from right to left
+        let mut windows: Vec<&Node> = ws.iter().filter(|n| n.is_any_window()).collect();
+        windows.reverse();
+        match self.options.apps {
+            RenameApps::Focused => focused.into_iter().collect(),
+            RenameApps::Tree => windows,
+            RenameApps::Mru => {
+                // the history doesn't have the window being focused yet
+                windows.sort_by_key(|w| {
+                    if Some(w.id) == focused.map(|f| f.id) {
+                        0
+                    } else {
+                        1 + self
+                            .mru
+                            .iter()
+                            .position(|id| *id == w.id)
+                            .unwrap_or(self.mru.len())
+                    }
+                });
+                windows
+            }
         }
+    }
 
-        let ws_num = focused_ws
-            .name
-            .split(":")
-            .next()
-            .unwrap_or(&focused_ws.name);
-        if let Some(app_name) = get_app_name(&event) {
-            let cmd = format!("rename workspace to {}: {}", ws_num, app_name);
-            log::debug!("workspace name manager, cmd: {}", cmd);
-            self.connection.run_command(cmd).await?;
+    /// The apps of the windows, each once with its number of windows.
+    fn apps_of(windows: &[&Node]) -> Vec<(String, usize)> {
+        let mut apps: Vec<(String, usize)> = Vec::new();
+        for app in windows.iter().filter_map(|w| get_app_name(w)) {
+            match apps.iter_mut().find(|(a, _)| *a == app) {
+                Some((_, count)) => *count += 1,
+                None => apps.push((app, 1)),
+            }
+        }
+        apps
+    }
+
+    /// The name the workspace should have, computed from its windows.
+    fn workspace_name(&self, workspace: &Workspace) -> Option<String> {
+        // names made from a template needn't start with "{num}:", so use the number sway parsed
+        let ws_num = if workspace.num >= 0 {
+            workspace.num.to_string()
         } else {
-            log::error!("workspace name manager failed to set a workspace name");
+            // the current name may be one persway rendered already
+            self.base_names
+                .lock()
+                .unwrap()
+                .entry(workspace.id)
+                .or_insert_with(|| workspace.name.clone())
+                .clone()
+        };
+        let ws = self.tree.get(workspace.id)?;
+        let focused = focused_window(ws);
+        let windows = self.named_after(ws, focused);
+        let name = match focused {
+            Some(focused) => {
+                let count = ws
+                    .iter()
+                    .filter(|n| n.is_any_window())
+                    .filter(|n| n.id != focused.id)
+                    .count();
+                self.options.render(
+                    &ws_num,
+                    &get_app_name(focused).unwrap_or_default(),
+                    &Self::apps_of(&windows),
+                    count,
+                )
+            }
+            // nothing to name an empty workspace after
+            None => ws_num,
+        };
+        Some(name)
+    }
+
+    /// Renames every workspace whose name no longer matches its windows, as
+    /// windows closing or moving away change workspaces that aren't focused.
+    async fn rename_workspaces(&mut self, event: WindowEvent) -> Result<()> {
+        log::debug!("workspace name manager handling event: {:?}", event.change);
+        let mut cmds = Vec::new();
+        self.base_names
+            .lock()
+            .unwrap()
+            .retain(|id, _| self.tree.workspaces().iter().any(|ws| ws.id == *id));
+        for workspace in self.tree.workspaces() {
+            if should_skip_rename_of_workspace(workspace) {
+                log::debug!("workspace name manager skip renaming workspace");
+                continue;
+            }
+            match self.workspace_name(workspace) {
+                Some(name) if name != workspace.name => cmds.push(format!(
+                    "rename workspace {} to {}",
+                    utils::quote(&workspace.name),
+                    utils::quote(&name)
+                )),
+                Some(_) => {}
+                None => log::error!(
+                    "workspace name manager, workspace {} not in the tree",
+                    workspace.name
+                ),
+            }
+        }
+        if !cmds.is_empty() {
+            let cmd = cmds.join("; ");
+            log::debug!("workspace name manager, cmd: {}", cmd);
+            self.commands.run_command(cmd).await?;
         }
 
         Ok(())
@@ -84,13 +309,8 @@ impl WorkspaceRenamer {
 impl WindowEventHandler for WorkspaceRenamer {
     async fn handle(&mut self, event: Box<WindowEvent>) {
         match event.change {
-            WindowChange::Focus => {
-                if let Err(e) = self.rename_workspace(*event).await {
-                    log::error!("workspace name manager, layout err: {}", e);
-                };
-            }
-            WindowChange::Close => {
-                if let Err(e) = self.rename_workspace(*event).await {
+            WindowChange::Focus | WindowChange::Close | WindowChange::Move => {
+                if let Err(e) = self.rename_workspaces(*event).await {
                     log::error!("workspace name manager, layout err: {}", e);
                 };
             }
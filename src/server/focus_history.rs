@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+/// How long after the last focus-prev or focus-next the next one keeps walking
+/// the history instead of starting over from the most recent window, like
+/// holding alt between presses of tab.
+const WALK_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Where focus-prev and focus-next have walked the history to.
+#[derive(Clone, Debug)]
+struct Walk {
+    index: usize,
+    at: Instant,
+}
+
+/// The windows of all workspaces, most recently focused first, and the window
+/// focus was last on, which differs from the first one while walking.
+#[derive(Clone, Debug, Default)]
+pub struct FocusHistory {
+    windows: Vec<i64>,
+    walk: Option<Walk>,
+    last_focused: Option<i64>,
+}
+
+impl FocusHistory {
+    pub fn windows(&self) -> &[i64] {
+        &self.windows
+    }
+
+    /// The window that has, or last had, focus.
+    pub fn last_focused(&self) -> Option<i64> {
+        self.last_focused
+    }
+
+    /// Moves the window to the front, unless it was focused by walking the
+    /// history.
+    pub fn focus(&mut self, id: i64) {
+        self.last_focused = Some(id);
+        if let Some(walk) = &self.walk {
+            if self.windows.get(walk.index) == Some(&id) {
+                return;
+            }
+        }
+        self.end_walk();
+        self.windows.retain(|w| *w != id);
+        self.windows.insert(0, id);
+    }
+
+    /// Moves the window the walk ended on to the front, it is the most recently
+    /// focused one now.
+    fn end_walk(&mut self) {
+        if let Some(walk) = self.walk.take() {
+            if walk.index < self.windows.len() {
+                let id = self.windows.remove(walk.index);
+                self.windows.insert(0, id);
+            }
+        }
+    }
+
+    pub fn close(&mut self, id: i64) {
+        if self.last_focused == Some(id) {
+            self.last_focused = None;
+        }
+        self.end_walk();
+        self.windows.retain(|w| *w != id);
+    }
+
+    /// Drops the windows that no longer exist.
+    pub fn retain(&mut self, exists: impl Fn(i64) -> bool) {
+        if self.windows.iter().any(|w| !exists(*w)) {
+            self.end_walk();
+            self.windows.retain(|w| exists(*w));
+        }
+    }
+
+    /// The window to focus next, going back to less recently focused windows or
+    /// forward to more recently focused ones, wrapping around at either end.
+    pub fn step(&mut self, back: bool) -> Option<i64> {
+        if self.windows.len() < 2 {
+            return None;
+        }
+        let index = match &self.walk {
+            Some(walk) if walk.at.elapsed() < WALK_TIMEOUT => walk.index,
+            _ => {
+                self.end_walk();
+                0
+            }
+        };
+        let len = self.windows.len();
+        let index = if back {
+            (index + 1) % len
+        } else {
+            (index + len - 1) % len
+        };
+        self.walk = Some(Walk {
+            index,
+            at: Instant::now(),
+        });
+        Some(self.windows[index])
+    }
+}
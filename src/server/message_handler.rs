@@ -1,154 +1,943 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use async_std::task;
-use swayipc_async::{Connection, WindowEvent};
+use clap::{CommandFactory, FromArgMatches};
+use futures::channel::mpsc::UnboundedSender;
+use swayipc_async::{Connection, Node, WindowChange, WindowEvent};
 
 use super::command_handlers;
+use super::command_runner::{CommandRunner, RunMode};
 use super::event_handlers;
+use super::event_handlers::layout::{bsp::Presel, columns::Lines, spiral::SpiralOptions};
+use super::event_handlers::misc::{
+    dimmer::{DimOptions, DimRunner, DimState},
+    exec_hooks::ExecPrograms,
+    window_hooks::WindowHookSet,
+    workspace_renamer::{BaseNames, RenameOptions},
+};
+use super::focus_history::FocusHistory;
+use super::urgency::Urgency;
 
-use crate::{commands::PerswayCommand, layout::WorkspaceLayout, utils};
+use crate::{
+    commands::{DaemonArgs, PerswayCommand},
+    criteria::{Criteria, Hook},
+    layout::{StackLayout, WorkspaceLayout},
+    node_ext::NodeExt,
+    tree_cache::TreeCache,
+    utils,
+};
 
 #[derive(Debug)]
 pub struct WorkspaceConfig {
     layout: WorkspaceLayout,
+    zoomed_from: Option<ZoomedFrom>,
+    presel: Option<Presel>,
+    // stack-push left the main area empty for the next window to open
+    pushed: bool,
+    // the options each layout was last used with on the workspace
+    layouts: HashMap<&'static str, WorkspaceLayout>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+/// How many arrangements of a workspace undo can go back.
+const HISTORY_SIZE: usize = 20;
+
+/// What a workspace looked like before toggle-zoom switched it to monocle.
+#[derive(Clone, Debug, PartialEq)]
+struct ZoomedFrom {
+    layout: WorkspaceLayout,
+    // window ids in the order the relayout should move them back
+    windows: Vec<i64>,
+}
+
+/// An arrangement of a workspace to go back to with undo, or forward to with redo.
+#[derive(Clone, Debug, PartialEq)]
+struct Snapshot {
+    layout: WorkspaceLayout,
+    zoomed_from: Option<ZoomedFrom>,
+    // window ids in the order the relayout should move them back
+    windows: Vec<i64>,
+}
+
+/// The windows of the workspace from left to right.
+fn window_order(wstree: &Node) -> Vec<i64> {
+    let mut windows: Vec<i64> = wstree
+        .iter()
+        .filter(|n| n.is_window())
+        .map(|n| n.id)
+        .collect();
+    windows.reverse();
+    windows
+}
+
+impl WorkspaceConfig {
+    fn new(layout: WorkspaceLayout) -> Self {
+        let mut layouts = HashMap::new();
+        layouts.insert(layout.name(), layout.clone());
+        Self {
+            layout,
+            zoomed_from: None,
+            presel: None,
+            pushed: false,
+            layouts,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    fn snapshot(&self, windows: Vec<i64>) -> Snapshot {
+        Snapshot {
+            layout: self.layout.clone(),
+            zoomed_from: self.zoomed_from.clone(),
+            windows,
+        }
+    }
+
+    /// Keeps the arrangement for undo to go back to, dropping what redo could go
+    /// forward to.
+    fn remember(&mut self, snapshot: Snapshot) {
+        if self.undo.last() == Some(&snapshot) {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push(snapshot);
+        if self.undo.len() > HISTORY_SIZE {
+            self.undo.remove(0);
+        }
+    }
+
+    /// The arrangement undo, or redo when not going back, would restore.
+    fn peek(&self, back: bool) -> Option<&Snapshot> {
+        if back {
+            self.undo.last()
+        } else {
+            self.redo.last()
+        }
+    }
+
+    /// Goes back or forward to the next arrangement in the history, keeping the
+    /// current one to return to.
+    fn travel(&mut self, back: bool, current: Snapshot) -> Option<Snapshot> {
+        let (from, to) = if back {
+            (&mut self.undo, &mut self.redo)
+        } else {
+            (&mut self.redo, &mut self.undo)
+        };
+        let snapshot = from.pop()?;
+        to.push(current);
+        self.set_layout(snapshot.layout.clone());
+        self.zoomed_from = snapshot.zoomed_from.clone();
+        Some(snapshot)
+    }
+
+    fn set_layout(&mut self, layout: WorkspaceLayout) {
+        self.layouts.insert(layout.name(), layout.clone());
+        self.layout = layout;
+        self.zoomed_from = None;
+        self.presel = None;
+        self.pushed = false;
+    }
+
+    /// The given layout with the options it was last used with on the workspace, if any.
+    fn remembered(&self, layout: &WorkspaceLayout) -> WorkspaceLayout {
+        self.layouts.get(layout.name()).unwrap_or(layout).clone()
+    }
 }
 
 #[derive(Debug)]
 pub struct MessageHandler {
     workspace_config: HashMap<i32, WorkspaceConfig>,
     default_layout: WorkspaceLayout,
-    workspace_renaming: bool,
+    rename_options: Option<RenameOptions>,
+    base_names: BaseNames,
     on_window_focus: Option<String>,
     on_window_focus_leave: Option<String>,
+    focus_hooks: Vec<Hook>,
+    focus_leave_hooks: Vec<Hook>,
+    window_hooks: WindowHookSet,
+    exec_programs: ExecPrograms,
+    dim_options: Option<DimOptions>,
+    dim_state: Arc<Mutex<DimState>>,
+    dim_runner: DimRunner,
+    focus_history: FocusHistory,
+    urgency: Urgency,
+    auto_focus_urgent: Vec<Criteria>,
+    subscribers: Vec<UnboundedSender<String>>,
+    status: String,
+    run_mode: RunMode,
 }
 
 impl MessageHandler {
-    pub fn new(
-        default_layout: WorkspaceLayout,
-        workspace_renaming: bool,
-        on_window_focus: Option<String>,
-        on_window_focus_leave: Option<String>,
-    ) -> Self {
+    pub fn from_args(args: DaemonArgs) -> Self {
+        let DaemonArgs {
+            default_layout,
+            stack_main_default_size,
+            stack_main_default_stack_layout,
+            workspace_renaming,
+            rename_format,
+            rename_apps,
+            app_name,
+            app_icon,
+            fallback_icon,
+            rename_max_length,
+            on_window_focus,
+            on_window_focus_leave,
+            on_focus,
+            on_focus_leave,
+            on_new,
+            on_close,
+            on_fullscreen,
+            on_floating,
+            on_urgent,
+            on_title,
+            on_mark,
+            exec_on_focus,
+            exec_on_new,
+            exec_on_close,
+            exec_on_urgent,
+            dim_inactive,
+            dim_active,
+            dim_fade,
+            dim_exclude,
+            auto_focus_urgent,
+            dry_run,
+            ..
+        } = args;
+        let default_layout = match default_layout {
+            WorkspaceLayout::StackMain { .. } => WorkspaceLayout::StackMain {
+                size: stack_main_default_size,
+                stack_layout: stack_main_default_stack_layout,
+            },
+            _ => default_layout,
+        };
         MessageHandler {
             workspace_config: HashMap::new(),
             default_layout,
-            workspace_renaming,
+            rename_options: workspace_renaming.then(|| RenameOptions {
+                format: rename_format,
+                apps: rename_apps,
+                names: app_name.into_iter().collect(),
+                icons: app_icon.into_iter().collect(),
+                fallback_icon,
+                max_length: rename_max_length.map(usize::from),
+            }),
+            base_names: BaseNames::default(),
             on_window_focus,
             on_window_focus_leave,
+            focus_hooks: on_focus,
+            focus_leave_hooks: on_focus_leave,
+            window_hooks: WindowHookSet {
+                new: on_new,
+                close: on_close,
+                fullscreen: on_fullscreen,
+                floating: on_floating,
+                urgent: on_urgent,
+                title: on_title,
+                mark: on_mark,
+            },
+            exec_programs: ExecPrograms {
+                focus: exec_on_focus,
+                new: exec_on_new,
+                close: exec_on_close,
+                urgent: exec_on_urgent,
+            },
+            dim_options: dim_inactive.map(|inactive| DimOptions {
+                active: dim_active,
+                inactive,
+                fade: Duration::from_millis(dim_fade),
+                exclude: dim_exclude,
+            }),
+            dim_state: Arc::new(Mutex::new(DimState::default())),
+            dim_runner: DimRunner::default(),
+            focus_history: FocusHistory::default(),
+            urgency: Urgency::default(),
+            auto_focus_urgent,
+            subscribers: Vec::new(),
+            status: String::new(),
+            run_mode: if dry_run {
+                RunMode::DryRun
+            } else {
+                RunMode::Execute
+            },
         }
     }
 
     pub fn get_workspace_config(&mut self, ws_num: i32) -> &WorkspaceConfig {
+        self.get_workspace_config_mut(ws_num)
+    }
+
+    fn get_workspace_config_mut(&mut self, ws_num: i32) -> &mut WorkspaceConfig {
         self.workspace_config
             .entry(ws_num)
-            .or_insert_with(|| WorkspaceConfig {
-                layout: self.default_layout.clone(),
-            })
+            .or_insert_with(|| WorkspaceConfig::new(self.default_layout.clone()))
     }
 
-    pub async fn handle_event(&mut self, event: Box<WindowEvent>) -> Result<()> {
-        log::debug!("controller.handle_event: {:?}", event.change);
+    /// Moves the windows of the workspace out of it and back in again so that
+    /// its layout is applied to them. Windows are moved back in the given
+    /// order, windows missing from it go last. The given window is focused
+    /// afterwards.
+    async fn relayout(
+        ws_num: i32,
+        mode: RunMode,
+        order: Vec<i64>,
+        focus: Option<i64>,
+    ) -> Result<()> {
+        log::debug!("start relayout of ws {}", ws_num);
+        let relayout = utils::relayout_workspace(
+            ws_num,
+            mode.clone(),
+            move |mut conn, ws_num, _old_ws_id, _output_id, windows| async move {
+                let mut windows: Vec<i64> = windows.iter().rev().map(|w| w.id).collect();
+                windows.sort_by_key(|id| order.iter().position(|o| o == id).unwrap_or(usize::MAX));
+                for id in windows {
+                    let cmd = format!(
+                        "[con_id={}] move to workspace number {}; [con_id={}] focus",
+                        id, ws_num, id
+                    );
+                    log::debug!("relayout closure cmd: {}", cmd);
+                    conn.run_command(cmd).await?;
+                    task::sleep(Duration::from_millis(50)).await;
+                }
+                if let Some(id) = focus {
+                    conn.run_command(format!("[con_id={}] focus", id)).await?;
+                }
+                Ok(())
+            },
+        );
+        if mode.is_explain() {
+            relayout.await?;
+        } else {
+            task::spawn(relayout);
+        }
+        Ok(())
+    }
+
+    /// Switches the workspace to the given layout and relayouts it, unless it
+    /// already uses that layout.
+    async fn change_layout(
+        &mut self,
+        ws_num: i32,
+        layout: WorkspaceLayout,
+        mode: RunMode,
+    ) -> Result<()> {
+        if self.get_workspace_config(ws_num).layout == layout {
+            log::debug!(
+                "no layout change of ws {} as the requested one was already set",
+                ws_num,
+            );
+            return Ok(());
+        }
+        log::debug!("change layout of ws {} to {}", ws_num, layout);
+        if !mode.is_explain() {
+            self.get_workspace_config_mut(ws_num).set_layout(layout);
+        }
+        Self::relayout(ws_num, mode, Vec::new(), None).await
+    }
+
+    /// Handles a batch of window events. The tree is fetched once for the whole
+    /// batch and shared by all handlers, and fetched again before an event when
+    /// commands sent since may have changed it.
+    pub async fn handle_events(&mut self, events: Vec<WindowEvent>) -> Result<()> {
         let mut conn = Connection::new().await?;
-        let ws = utils::get_focused_workspace(&mut conn).await?;
-        match &self.get_workspace_config(ws.num).layout {
-            WorkspaceLayout::Spiral => {
+        let tree = TreeCache::fetch(&mut conn).await?;
+        self.handle_batch(events, tree, Some(&mut conn)).await
+    }
+
+    /// Handles a batch of window events against the given tree rather than the
+    /// current one, see `persway replay`.
+    pub async fn handle_events_with_tree(
+        &mut self,
+        events: Vec<WindowEvent>,
+        tree: TreeCache,
+    ) -> Result<()> {
+        self.handle_batch(events, tree, None).await
+    }
+
+    /// Handles the events one by one, an event failing is logged and doesn't
+    /// keep the others from being handled. With a connection the tree is
+    /// refetched whenever commands computed from it were sent.
+    async fn handle_batch(
+        &mut self,
+        events: Vec<WindowEvent>,
+        tree: TreeCache,
+        mut connection: Option<&mut Connection>,
+    ) -> Result<()> {
+        let mut tree = Arc::new(tree);
+        if let RunMode::DryRun = self.run_mode {
+            if let Some(ws) = tree.focused_workspace() {
+                log::info!(
+                    "dry-run tree:\n{}",
+                    tree.describe(ws.id).unwrap_or_default()
+                );
+            }
+        }
+        for event in events {
+            if let Some(conn) = connection.as_deref_mut() {
+                if tree.is_stale() {
+                    match TreeCache::fetch(conn).await {
+                        Ok(fresh) => tree = Arc::new(fresh),
+                        Err(e) => log::error!("refetching the tree failed: {}", e),
+                    }
+                }
+            }
+            let change = event.change;
+            if let Err(e) = self.handle_event(Box::new(event), Arc::clone(&tree)).await {
+                log::error!("handling {:?} event failed: {}", change, e);
+            }
+        }
+        self.notify_subscribers()?;
+        Ok(())
+    }
+
+    fn status(&self) -> Result<String> {
+        let mut status = serde_json::to_string(&self.urgency.status())?;
+        status.push('\n');
+        Ok(status)
+    }
+
+    /// Sends the status to the clients running persway subscribe when it changed.
+    fn notify_subscribers(&mut self) -> Result<()> {
+        let status = self.status()?;
+        if status != self.status {
+            self.subscribers
+                .retain(|subscriber| subscriber.unbounded_send(status.clone()).is_ok());
+            self.status = status;
+        }
+        Ok(())
+    }
+
+    /// Sends the status to the given client now and whenever it changes.
+    pub fn subscribe(&mut self, subscriber: UnboundedSender<String>) -> Result<()> {
+        if subscriber.unbounded_send(self.status()?).is_ok() {
+            self.subscribers.push(subscriber);
+        }
+        Ok(())
+    }
+
+    /// Focuses windows becoming urgent that match the --auto-focus-urgent criteria.
+    async fn auto_focus_urgent(&self, event: &WindowEvent, tree: &TreeCache) -> Result<()> {
+        if event.change == WindowChange::Urgent
+            && event.container.urgent
+            && self
+                .auto_focus_urgent
+                .iter()
+                .any(|criteria| criteria.matches(tree, &event.container))
+        {
+            let mut commands = CommandRunner::for_tree(self.run_mode.clone(), tree).await?;
+            commands
+                .run_command(format!("[con_id={}] focus", event.container.id))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, event: Box<WindowEvent>, tree: Arc<TreeCache>) -> Result<()> {
+        log::debug!("controller.handle_event: {:?}", event.change);
+        self.urgency.update(&event);
+        if let Err(e) = self.auto_focus_urgent(&event, &tree).await {
+            log::error!("auto focus of urgent window failed: {}", e);
+        }
+        let ws_num = tree
+            .focused_workspace()
+            .context("no focused workspace")?
+            .num;
+        let layout = &self.get_workspace_config(ws_num).layout;
+        match layout {
+            WorkspaceLayout::Spiral {
+                ratio,
+                max_depth,
+                overflow_layout,
+            } => {
                 log::debug!("handling event via spiral manager");
+                let options = SpiralOptions {
+                    ratio: *ratio,
+                    direction: None,
+                    max_depth: *max_depth,
+                    overflow_layout: overflow_layout.clone(),
+                };
+                task::spawn(event_handlers::layout::spiral::Spiral::handle(
+                    event.clone(),
+                    Arc::clone(&tree),
+                    options,
+                    self.run_mode.clone(),
+                ));
+            }
+            WorkspaceLayout::Dwindle { ratio, direction } => {
+                log::debug!("handling event via spiral manager as dwindle");
+                let options = SpiralOptions {
+                    ratio: Some(*ratio),
+                    direction: direction.clone(),
+                    max_depth: None,
+                    overflow_layout: StackLayout::Tabbed,
+                };
                 task::spawn(event_handlers::layout::spiral::Spiral::handle(
                     event.clone(),
+                    Arc::clone(&tree),
+                    options,
+                    self.run_mode.clone(),
                 ));
             }
             WorkspaceLayout::StackMain { stack_layout, size } => {
                 log::debug!("handling event via stack_main manager");
+                let (size, stack_layout) = (*size, stack_layout.clone());
+                let config = self.get_workspace_config_mut(ws_num);
+                let pushed = config.pushed;
+                if event.change == WindowChange::New {
+                    config.pushed = false;
+                }
                 task::spawn(event_handlers::layout::stack_main::StackMain::handle(
                     event.clone(),
-                    *size,
-                    stack_layout.clone(),
+                    Arc::clone(&tree),
+                    size,
+                    stack_layout,
+                    pushed,
+                    self.run_mode.clone(),
+                ));
+            }
+            WorkspaceLayout::Monocle => {
+                log::debug!("handling event via monocle manager");
+                task::spawn(event_handlers::layout::monocle::Monocle::handle(
+                    event.clone(),
+                    Arc::clone(&tree),
+                    self.run_mode.clone(),
                 ));
             }
+            WorkspaceLayout::Columns { max } | WorkspaceLayout::Rows { max } => {
+                log::debug!("handling event via columns manager");
+                let lines = match layout {
+                    WorkspaceLayout::Columns { .. } => Lines::Columns,
+                    _ => Lines::Rows,
+                };
+                task::spawn(event_handlers::layout::columns::Columns::handle(
+                    event.clone(),
+                    Arc::clone(&tree),
+                    lines,
+                    *max,
+                    self.run_mode.clone(),
+                ));
+            }
+            WorkspaceLayout::Scrolling { width } => {
+                log::debug!("handling event via scrolling manager");
+                // awaited so that the next event sees the columns it moved
+                event_handlers::layout::scrolling::Scrolling::handle(
+                    event.clone(),
+                    Arc::clone(&tree),
+                    *width,
+                    self.run_mode.clone(),
+                )
+                .await;
+            }
+            WorkspaceLayout::Bsp => {
+                log::debug!("handling event via bsp manager");
+                let config = self.get_workspace_config_mut(ws_num);
+                let presel = config.presel.clone();
+                let consumed = presel.as_ref().is_some_and(|presel| match event.change {
+                    WindowChange::New => presel.applies_to(&tree, event.container.id),
+                    WindowChange::Close => presel.window == event.container.id,
+                    _ => false,
+                });
+                if consumed {
+                    config.presel = None;
+                }
+                // awaited so that a window opened by a preselection is moved into
+                // place before its focus event splits it
+                event_handlers::layout::bsp::Bsp::handle(
+                    event.clone(),
+                    Arc::clone(&tree),
+                    presel,
+                    self.run_mode.clone(),
+                )
+                .await;
+            }
             WorkspaceLayout::Manual => {}
         };
-        if self.workspace_renaming {
-            event_handlers::misc::workspace_renamer::WorkspaceRenamer::handle(event.clone()).await;
+        if let Some(options) = &self.rename_options {
+            event_handlers::misc::workspace_renamer::WorkspaceRenamer::handle(
+                event.clone(),
+                Arc::clone(&tree),
+                options.clone(),
+                self.focus_history.windows().to_vec(),
+                Arc::clone(&self.base_names),
+                self.run_mode.clone(),
+            )
+            .await;
+        }
+        let previously_focused_id = self
+            .focus_history
+            .last_focused()
+            .filter(|id| *id != event.container.id);
+        match event.change {
+            WindowChange::Focus => self.focus_history.focus(event.container.id),
+            WindowChange::Close => self.focus_history.close(event.container.id),
+            _ => {}
         }
         event_handlers::misc::window_focus::WindowFocus::handle(
             event.clone(),
             self.on_window_focus.clone(),
             self.on_window_focus_leave.clone(),
+            previously_focused_id,
+            self.run_mode.clone(),
         )
         .await;
+        if let Some(options) = &self.dim_options {
+            event_handlers::misc::dimmer::Dimmer::handle(
+                event.clone(),
+                Arc::clone(&tree),
+                options.clone(),
+                Arc::clone(&self.dim_state),
+                self.dim_runner.clone(),
+                previously_focused_id,
+                self.run_mode.clone(),
+            )
+            .await;
+        }
+        let hooks = self.window_hooks.for_event(&event);
+        if !hooks.is_empty() {
+            event_handlers::misc::window_hooks::WindowHooks::handle(
+                event.clone(),
+                Arc::clone(&tree),
+                hooks.to_vec(),
+                self.run_mode.clone(),
+            )
+            .await;
+        }
+        let programs = self.exec_programs.for_event(&event);
+        if !programs.is_empty() {
+            event_handlers::misc::exec_hooks::ExecHooks::handle(
+                event.clone(),
+                Arc::clone(&tree),
+                programs.to_vec(),
+                self.get_workspace_config(ws_num).layout.name().to_string(),
+                self.run_mode.clone(),
+            )
+            .await;
+        }
+        if !self.focus_hooks.is_empty() || !self.focus_leave_hooks.is_empty() {
+            event_handlers::misc::focus_hooks::FocusHooks::handle(
+                event.clone(),
+                Arc::clone(&tree),
+                self.focus_hooks.clone(),
+                self.focus_leave_hooks.clone(),
+                previously_focused_id,
+                self.run_mode.clone(),
+            )
+            .await;
+        }
         Ok(())
     }
-    pub async fn handle_command(&mut self, cmd: PerswayCommand) -> Result<()> {
+    /// Handles a command from a client, returning the response for commands
+    /// that produce output.
+    pub async fn handle_command(&mut self, cmd: PerswayCommand) -> Result<Option<String>> {
         log::debug!("controller.handle_command: {:?}", cmd);
+        match cmd {
+            PerswayCommand::Explain { command } => {
+                let matches = PerswayCommand::command().try_get_matches_from(
+                    std::iter::once("persway").chain(command.iter().map(String::as_str)),
+                )?;
+                let mut command = PerswayCommand::from_arg_matches(&matches)?;
+                command.note_given_options(&matches);
+                let commands = Arc::new(Mutex::new(Vec::new()));
+                let tree = self
+                    .dispatch_command(command, RunMode::Explain(Arc::clone(&commands)))
+                    .await?;
+                let ws = tree.focused_workspace().context("no focused workspace")?;
+                let mut explanation = String::from("tree:\n");
+                explanation.push_str(&tree.describe(ws.id).unwrap_or_default());
+                explanation.push_str("commands:\n");
+                for cmd in commands.lock().unwrap().iter() {
+                    explanation.push_str(cmd);
+                    explanation.push('\n');
+                }
+                Ok(Some(explanation))
+            }
+            PerswayCommand::Status => Ok(Some(self.status()?)),
+            PerswayCommand::ListWindows { mru, format } => {
+                let mut conn = Connection::new().await?;
+                let tree = Arc::new(TreeCache::fetch(&mut conn).await?);
+                let list = command_handlers::misc::window_list::WindowList::new(
+                    tree,
+                    self.run_mode.clone(),
+                )
+                .await?;
+                let history = mru.then(|| self.focus_history.windows());
+                let windows = list.list(format, history, |ws_num| {
+                    let layout = self
+                        .workspace_config
+                        .get(&ws_num)
+                        .map_or(&self.default_layout, |config| &config.layout);
+                    matches!(layout, WorkspaceLayout::StackMain { .. })
+                })?;
+                Ok(Some(windows))
+            }
+            cmd => {
+                self.dispatch_command(cmd, self.run_mode.clone()).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs a command, returning the tree it was computed from.
+    async fn dispatch_command(
+        &mut self,
+        cmd: PerswayCommand,
+        mode: RunMode,
+    ) -> Result<Arc<TreeCache>> {
         let mut conn = Connection::new().await?;
-        let ws = utils::get_focused_workspace(&mut conn).await?;
+        let tree = Arc::new(TreeCache::fetch(&mut conn).await?);
+        let ws = tree
+            .focused_workspace()
+            .context("no focused workspace")?
+            .clone();
+        // taken before the command runs, kept for undo once it did rearrange
+        let before = if !mode.is_explain() && cmd.rearranges() {
+            let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+            let config = self.get_workspace_config(ws.num);
+            Some(config.snapshot(window_order(wstree)))
+        } else {
+            None
+        };
+        let mut rearranged = false;
         let current_ws_config = self.get_workspace_config(ws.num);
         match cmd {
-            PerswayCommand::ChangeLayout { layout } => {
-                if current_ws_config.layout != layout {
-                    self.workspace_config
-                        .entry(ws.num)
-                        .and_modify(|e| e.layout = layout.clone())
-                        .or_insert_with(|| WorkspaceConfig {
-                            layout: layout.clone(),
-                        });
-                    log::debug!("change layout of ws {} to {}", ws.num, layout);
-                    log::debug!("start relayout of ws {}", ws.num);
-                    task::spawn(utils::relayout_workspace(
-                        ws.num,
-                        |mut conn, ws_num, _old_ws_id, _output_id, windows| async move {
-                            for window in windows.iter().rev() {
-                                let cmd = format!(
-                                    "[con_id={}] move to workspace number {}; [con_id={}] focus",
-                                    window.id, ws_num, window.id
-                                );
-                                log::debug!("relayout closure cmd: {}", cmd);
-                                conn.run_command(cmd).await?;
-                                task::sleep(Duration::from_millis(50)).await;
-                            }
-                            Ok(())
-                        },
-                    ));
+            PerswayCommand::ChangeLayout { layout, given } => {
+                let layout = current_ws_config
+                    .remembered(&layout)
+                    .with_options(layout, &given);
+                rearranged = layout != current_ws_config.layout;
+                self.change_layout(ws.num, layout, mode).await?;
+            }
+            PerswayCommand::CycleLayout { layouts } => {
+                let next = layouts
+                    .iter()
+                    .position(|l| l.name() == current_ws_config.layout.name())
+                    .map_or(0, |i| (i + 1) % layouts.len());
+                let layout = current_ws_config.remembered(&layouts[next]);
+                rearranged = layout != current_ws_config.layout;
+                self.change_layout(ws.num, layout, mode).await?;
+            }
+            PerswayCommand::ToggleLayout { first, second } => {
+                let layout = if current_ws_config.layout.name() == first.name() {
+                    current_ws_config.remembered(&second)
                 } else {
-                    log::debug!(
-                        "no layout change of ws {} as the requested one was already set",
-                        ws.num,
-                    );
+                    current_ws_config.remembered(&first)
+                };
+                rearranged = layout != current_ws_config.layout;
+                self.change_layout(ws.num, layout, mode).await?;
+            }
+            PerswayCommand::ToggleZoom => {
+                let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+                let focus = wstree
+                    .find_as_ref(|n| n.is_window() && n.focused)
+                    .map(|n| n.id);
+                let (layout, zoomed_from, order) = match &current_ws_config.zoomed_from {
+                    Some(zoomed_from) => (
+                        zoomed_from.layout.clone(),
+                        None,
+                        zoomed_from.windows.clone(),
+                    ),
+                    None if current_ws_config.layout == WorkspaceLayout::Monocle => {
+                        return Err(anyhow!(
+                            "workspace {} already uses the monocle layout",
+                            ws.num
+                        ));
+                    }
+                    None => {
+                        let windows = window_order(wstree);
+                        let zoomed_from = ZoomedFrom {
+                            layout: current_ws_config.layout.clone(),
+                            windows: windows.clone(),
+                        };
+                        (WorkspaceLayout::Monocle, Some(zoomed_from), windows)
+                    }
+                };
+                log::debug!("toggle zoom of ws {}, layout {}", ws.num, layout);
+                if !mode.is_explain() {
+                    let config = self.get_workspace_config_mut(ws.num);
+                    config.set_layout(layout);
+                    config.zoomed_from = zoomed_from;
                 }
+                rearranged = true;
+                Self::relayout(ws.num, mode, order, focus).await?;
             }
-            PerswayCommand::StackFocusNext => {
-                if let WorkspaceLayout::StackMain { .. } = current_ws_config.layout {
-                    let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
-                    ctrl.stack_focus_next().await?
+            PerswayCommand::FocusWindow { window } => {
+                let mut list =
+                    command_handlers::misc::window_list::WindowList::new(Arc::clone(&tree), mode)
+                        .await?;
+                list.focus(&window).await?;
+            }
+            PerswayCommand::FocusUrgent => {
+                let id = self.urgency.oldest().context("no urgent window")?;
+                let mut commands = CommandRunner::new(mode).await?;
+                commands
+                    .run_command(format!("[con_id={}] focus", id))
+                    .await?;
+            }
+            PerswayCommand::ListWindows { .. }
+            | PerswayCommand::Status
+            | PerswayCommand::Subscribe => {}
+            PerswayCommand::Undo | PerswayCommand::Redo => {
+                let back = matches!(cmd, PerswayCommand::Undo);
+                let wstree = tree.get(ws.id).context("workspace not found in tree")?;
+                let focus = wstree
+                    .find_as_ref(|n| n.is_window() && n.focused)
+                    .map(|n| n.id);
+                let current = current_ws_config.snapshot(window_order(wstree));
+                let snapshot = if mode.is_explain() {
+                    current_ws_config.peek(back).cloned()
+                } else {
+                    self.get_workspace_config_mut(ws.num).travel(back, current)
+                };
+                let snapshot = snapshot.with_context(|| {
+                    format!(
+                        "nothing to {} on ws {}",
+                        if back { "undo" } else { "redo" },
+                        ws.num
+                    )
+                })?;
+                log::debug!("restore ws {} to layout {}", ws.num, snapshot.layout);
+                Self::relayout(ws.num, mode, snapshot.windows, focus).await?;
+            }
+            PerswayCommand::FocusPrev | PerswayCommand::FocusNext => {
+                let back = matches!(cmd, PerswayCommand::FocusPrev);
+                let mut explained;
+                let history = if mode.is_explain() {
+                    explained = self.focus_history.clone();
+                    &mut explained
+                } else {
+                    &mut self.focus_history
+                };
+                history.retain(|id| tree.get(id).is_some_and(|n| n.is_any_window()));
+                if let Some(id) = history.step(back) {
+                    let mut commands = CommandRunner::new(mode).await?;
+                    commands
+                        .run_command(format!("[con_id={}] focus", id))
+                        .await?;
                 }
             }
-            PerswayCommand::StackFocusPrev => {
+            stack_cmd @ (PerswayCommand::StackFocusNext
+            | PerswayCommand::StackFocusPrev
+            | PerswayCommand::StackMainRotatePrev
+            | PerswayCommand::StackMainRotateNext
+            | PerswayCommand::StackSwapMain
+            | PerswayCommand::StackFocusMain
+            | PerswayCommand::StackMoveUp
+            | PerswayCommand::StackMoveDown
+            | PerswayCommand::StackPush
+            | PerswayCommand::StackFocusIndex { .. }) => {
+                // the stack commands do nothing on workspaces with other layouts
                 if let WorkspaceLayout::StackMain { .. } = current_ws_config.layout {
-                    let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
-                    ctrl.stack_focus_prev().await?
+                    let explain = mode.is_explain();
+                    let mut ctrl = command_handlers::layout::stack_main::StackMain::new(
+                        Arc::clone(&tree),
+                        mode,
+                    )
+                    .await?;
+                    rearranged = true;
+                    match stack_cmd {
+                        PerswayCommand::StackFocusNext => ctrl.stack_focus_next().await?,
+                        PerswayCommand::StackFocusPrev => ctrl.stack_focus_prev().await?,
+                        PerswayCommand::StackMainRotatePrev => {
+                            ctrl.stack_main_rotate_prev().await?
+                        }
+                        PerswayCommand::StackMainRotateNext => {
+                            ctrl.stack_main_rotate_next().await?
+                        }
+                        PerswayCommand::StackSwapMain => ctrl.stack_swap_main().await?,
+                        PerswayCommand::StackFocusMain => ctrl.stack_focus_main().await?,
+                        PerswayCommand::StackMoveUp => ctrl.stack_move_up().await?,
+                        PerswayCommand::StackMoveDown => ctrl.stack_move_down().await?,
+                        PerswayCommand::StackPush => {
+                            rearranged = ctrl.stack_push().await?;
+                            self.get_workspace_config_mut(ws.num).pushed |= rearranged && !explain;
+                        }
+                        PerswayCommand::StackFocusIndex { index } => {
+                            ctrl.stack_focus_index(index as usize).await?
+                        }
+                        _ => {}
+                    }
                 }
             }
-            PerswayCommand::StackMainRotatePrev => {
-                if let WorkspaceLayout::StackMain { .. } = current_ws_config.layout {
-                    let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
-                    ctrl.stack_main_rotate_prev().await?
+            PerswayCommand::ScrollLeft => {
+                if let WorkspaceLayout::Scrolling { .. } = current_ws_config.layout {
+                    let mut ctrl = command_handlers::layout::scrolling::Scrolling::new(
+                        Arc::clone(&tree),
+                        mode,
+                    )
+                    .await?;
+                    ctrl.scroll_left().await?
                 }
             }
-            PerswayCommand::StackMainRotateNext => {
-                if let WorkspaceLayout::StackMain { .. } = current_ws_config.layout {
-                    let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
-                    ctrl.stack_main_rotate_next().await?
+            PerswayCommand::ScrollRight => {
+                if let WorkspaceLayout::Scrolling { .. } = current_ws_config.layout {
+                    let mut ctrl = command_handlers::layout::scrolling::Scrolling::new(
+                        Arc::clone(&tree),
+                        mode,
+                    )
+                    .await?;
+                    ctrl.scroll_right().await?
                 }
             }
-            PerswayCommand::StackSwapMain => {
-                if let WorkspaceLayout::StackMain { .. } = current_ws_config.layout {
-                    let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
-                    ctrl.stack_swap_main().await?
+            PerswayCommand::ColumnWidth { width } => {
+                if let WorkspaceLayout::Scrolling { .. } = current_ws_config.layout {
+                    if !mode.is_explain() {
+                        self.get_workspace_config_mut(ws.num)
+                            .set_layout(WorkspaceLayout::Scrolling { width });
+                    }
+                    let mut ctrl = command_handlers::layout::scrolling::Scrolling::new(
+                        Arc::clone(&tree),
+                        mode,
+                    )
+                    .await?;
+                    rearranged = true;
+                    ctrl.column_width(width).await?
+                }
+            }
+            PerswayCommand::Presel { direction } => {
+                if let WorkspaceLayout::Bsp = current_ws_config.layout {
+                    let mut ctrl =
+                        command_handlers::layout::bsp::Bsp::new(Arc::clone(&tree), mode.clone())
+                            .await?;
+                    let presel = ctrl.presel(direction).await?;
+                    if !mode.is_explain() {
+                        self.get_workspace_config_mut(ws.num).presel = presel;
+                    }
+                }
+            }
+            PerswayCommand::Rotate { parent } => {
+                if let WorkspaceLayout::Bsp = current_ws_config.layout {
+                    let mut ctrl =
+                        command_handlers::layout::bsp::Bsp::new(Arc::clone(&tree), mode).await?;
+                    rearranged = true;
+                    ctrl.rotate(parent).await?
+                }
+            }
+            PerswayCommand::Flip {
+                orientation,
+                parent,
+            } => {
+                if let WorkspaceLayout::Bsp = current_ws_config.layout {
+                    let mut ctrl =
+                        command_handlers::layout::bsp::Bsp::new(Arc::clone(&tree), mode).await?;
+                    rearranged = true;
+                    ctrl.flip(orientation, parent).await?
                 }
             }
-            PerswayCommand::Daemon(_) => unreachable!(),
+            PerswayCommand::Daemon(_)
+            | PerswayCommand::Explain { .. }
+            | PerswayCommand::Record { .. }
+            | PerswayCommand::Replay { .. } => {
+                return Err(anyhow!("can't run {:?} through the daemon", cmd));
+            }
         }
-        Ok(())
+        if let Some(snapshot) = before.filter(|_| rearranged) {
+            self.get_workspace_config_mut(ws.num).remember(snapshot);
+        }
+        Ok(tree)
     }
 }
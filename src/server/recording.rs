@@ -0,0 +1,74 @@
+use super::message_handler::MessageHandler;
+use crate::{commands::DaemonArgs, tree_cache::TreeCache};
+use anyhow::{Context, Result};
+use async_std::{fs::File, prelude::*, task};
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use swayipc_async::{Connection, Event, EventType, Node, WindowEvent, Workspace};
+
+/// One line of a recording: an event and the tree persway would have seen when
+/// handling it.
+#[derive(Serialize, Deserialize)]
+struct RecordedEvent {
+    event: WindowEvent,
+    tree: Node,
+    workspaces: Vec<Workspace>,
+}
+
+pub async fn record(path: &str) -> Result<()> {
+    let mut file = File::create(path).await?;
+    let mut connection = Connection::new().await?;
+    let mut events = Connection::new()
+        .await?
+        .subscribe([EventType::Window])
+        .await?;
+    log::info!("recording window events to {}", path);
+    while let Some(event) = events.next().await {
+        let event = match event? {
+            Event::Window(event) => *event,
+            _ => continue,
+        };
+        log::info!(
+            "recording {:?} event of con_id={}",
+            event.change,
+            event.container.id
+        );
+        let recorded = RecordedEvent {
+            event,
+            tree: connection.get_tree().await?,
+            workspaces: connection.get_workspaces().await?,
+        };
+        let mut line = serde_json::to_string(&recorded)?;
+        line.push('\n');
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+    }
+    Ok(())
+}
+
+pub async fn replay(path: &str, delay: u64, args: DaemonArgs) -> Result<()> {
+    let recording = async_std::fs::read_to_string(path).await?;
+    let mut message_handler = MessageHandler::from_args(args);
+    for (i, line) in recording.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let RecordedEvent {
+            event,
+            tree,
+            workspaces,
+        } = serde_json::from_str(line)
+            .with_context(|| format!("invalid event on line {} of {}", i + 1, path))?;
+        log::info!(
+            "replaying {:?} event of con_id={}",
+            event.change,
+            event.container.id
+        );
+        message_handler
+            .handle_events_with_tree(vec![event], TreeCache::new(tree, workspaces))
+            .await?;
+        task::sleep(Duration::from_millis(delay)).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use serde::Serialize;
+use swayipc_async::{WindowChange, WindowEvent};
+
+/// The windows asking for attention, those that became urgent first first.
+#[derive(Debug, Default)]
+pub struct Urgency {
+    windows: Vec<i64>,
+}
+
+/// What persway status prints and persway subscribe prints on every change,
+/// eg. for a bar to show a badge.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Status {
+    pub urgent: usize,
+    pub urgent_windows: Vec<i64>,
+}
+
+impl Urgency {
+    /// Keeps track of windows becoming urgent, and of urgent windows being
+    /// focused or closed.
+    pub fn update(&mut self, event: &WindowEvent) {
+        let id = event.container.id;
+        let urgent = match event.change {
+            WindowChange::Urgent => event.container.urgent,
+            WindowChange::Focus | WindowChange::Close => false,
+            _ => return,
+        };
+        self.windows.retain(|w| *w != id || urgent);
+        if urgent && !self.windows.contains(&id) {
+            self.windows.push(id);
+        }
+    }
+
+    /// The window that has been urgent the longest.
+    pub fn oldest(&self) -> Option<i64> {
+        self.windows.first().copied()
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            urgent: self.windows.len(),
+            urgent_windows: self.windows.clone(),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::node_ext::{NodeExt, RefinedNodeType};
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use swayipc_async::{Connection, Node, NodeLayout, NodeType, Workspace};
+
+struct NodeEntry {
+    // child indices from the root, indexing into nodes and then floating_nodes
+    path: Vec<usize>,
+    parent: Option<i64>,
+    workspace: Option<i64>,
+}
+
+/// A snapshot of the sway tree and workspaces, indexed by node id so that
+/// parent and workspace lookups don't need to go back to sway.
+///
+/// The snapshot is only accurate until the next command is sent to sway.
+/// Handlers that act on the outcome of their own commands fetch it again, and
+/// the daemon refetches it within an event batch once a command computed from
+/// it has been sent, see `is_stale`.
+pub struct TreeCache {
+    root: Node,
+    workspaces: Vec<Workspace>,
+    index: HashMap<i64, NodeEntry>,
+    stale: Arc<AtomicBool>,
+}
+
+impl TreeCache {
+    pub async fn fetch(connection: &mut Connection) -> Result<Self> {
+        let root = connection.get_tree().await?;
+        let workspaces = connection.get_workspaces().await?;
+        Ok(Self::new(root, workspaces))
+    }
+
+    pub fn new(root: Node, workspaces: Vec<Workspace>) -> Self {
+        let mut index = HashMap::new();
+        let mut stack = vec![(&root, Vec::new(), None, None)];
+        while let Some((node, path, parent, workspace)) = stack.pop() {
+            let workspace = if node.node_type == NodeType::Workspace {
+                Some(node.id)
+            } else {
+                workspace
+            };
+            for (i, child) in node.nodes.iter().chain(&node.floating_nodes).enumerate() {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                stack.push((child, child_path, Some(node.id), workspace));
+            }
+            index.insert(
+                node.id,
+                NodeEntry {
+                    path,
+                    parent,
+                    workspace,
+                },
+            );
+        }
+        Self {
+            root,
+            workspaces,
+            index,
+            stale: Arc::default(),
+        }
+    }
+
+    /// Whether a command runner made for this snapshot has sent a command,
+    /// see `CommandRunner::for_tree`.
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::SeqCst)
+    }
+
+    pub fn stale_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stale)
+    }
+
+    pub fn get(&self, id: i64) -> Option<&Node> {
+        let entry = self.index.get(&id)?;
+        let mut node = &self.root;
+        for &i in &entry.path {
+            node = if i < node.nodes.len() {
+                &node.nodes[i]
+            } else {
+                &node.floating_nodes[i - node.nodes.len()]
+            };
+        }
+        Some(node)
+    }
+
+    pub fn parent_of(&self, id: i64) -> Option<&Node> {
+        self.get(self.index.get(&id)?.parent?)
+    }
+
+    pub fn workspace_of(&self, id: i64) -> Option<&Workspace> {
+        let ws_id = self.index.get(&id)?.workspace?;
+        self.workspaces.iter().find(|w| w.id == ws_id)
+    }
+
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    pub fn focused_workspace(&self) -> Option<&Workspace> {
+        self.workspaces.iter().find(|w| w.focused)
+    }
+
+    /// Renders the subtree of the node with the given id, one node per line
+    /// including the con ids that persway's commands refer to.
+    pub fn describe(&self, id: i64) -> Option<String> {
+        let mut description = String::new();
+        describe_node(self.get(id)?, 0, &mut description);
+        Some(description)
+    }
+}
+
+fn describe_node(node: &Node, depth: usize, description: &mut String) {
+    let layout = match node.layout {
+        NodeLayout::SplitH => "splith",
+        NodeLayout::SplitV => "splitv",
+        NodeLayout::Stacked => "stacked",
+        NodeLayout::Tabbed => "tabbed",
+        NodeLayout::Output => "output",
+        NodeLayout::Dockarea => "dockarea",
+        _ => "none",
+    };
+    let mut line = match node.get_refined_node_type() {
+        RefinedNodeType::Workspace => format!(
+            "workspace {} [con_id={}] {}",
+            node.name.as_deref().unwrap_or_default(),
+            node.id,
+            layout
+        ),
+        RefinedNodeType::Window | RefinedNodeType::FloatingWindow => format!(
+            "window [con_id={}] {:?}",
+            node.id,
+            node.name.as_deref().unwrap_or_default()
+        ),
+        _ => format!("container [con_id={}] {}", node.id, layout),
+    };
+    if node.is_floating() {
+        line.push_str(" floating");
+    }
+    if node.focused {
+        line.push_str(" focused");
+    }
+    for mark in &node.marks {
+        line.push_str(&format!(" mark={}", mark));
+    }
+    description.push_str(&"  ".repeat(depth));
+    description.push_str(&line);
+    description.push('\n');
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        describe_node(child, depth + 1, description);
+    }
+}
@@ -1,17 +1,13 @@
 use crate::node_ext::NodeExt;
+use crate::server::command_runner::{CommandRunner, RunMode};
 use anyhow::{Context, Result};
 use async_std::task;
 use std::{future::Future, time::Duration};
-use swayipc_async::{Connection, Node, Workspace};
+use swayipc_async::{Node, Workspace};
 
 pub const PERSWAY_TMP_WORKSPACE: &str = "◕‿◕";
 pub const SCRATCHPAD_WORKSPACE: &str = "__i3_scratch";
 
-pub async fn get_focused_workspace(conn: &mut Connection) -> Result<Workspace> {
-    let mut ws = conn.get_workspaces().await?.into_iter();
-    ws.find(|w| w.focused).context("no focused workspace")
-}
-
 pub fn get_socket_path(socket_path: Option<String>) -> String {
     let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR");
     let wayland_display = std::env::var("WAYLAND_DISPLAY");
@@ -44,25 +40,26 @@ pub fn is_persway_tmp_workspace(ws: &Workspace) -> bool {
     ws.name == PERSWAY_TMP_WORKSPACE
 }
 
-pub async fn relayout_workspace<F, C>(ws_num: i32, f: C) -> Result<()>
+/// Quotes a workspace name for a sway command, as it may contain `,` or `;`.
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub async fn relayout_workspace<F, C>(ws_num: i32, mode: RunMode, f: C) -> Result<()>
 where
     F: Future<Output = Result<()>>,
-    C: FnOnce(Connection, i32, i64, i64, Vec<Node>) -> F,
+    C: FnOnce(CommandRunner, i32, i64, i64, Vec<Node>) -> F,
 {
-    let mut connection = Connection::new().await?;
-    let tree = connection.get_tree().await?;
-    let workspaces = connection.get_workspaces().await?;
+    let mut commands = CommandRunner::new(mode.clone()).await?;
+    let tree = commands.connection().get_tree().await?;
+    let workspaces = commands.connection().get_workspaces().await?;
     let output = tree
         .iter()
-        .find(|n| {
-            n.is_output()
-                && n.iter()
-                    .any(|n| n.is_workspace() && n.num.unwrap() == ws_num)
-        })
+        .find(|n| n.is_output() && n.iter().any(|n| n.is_workspace() && n.num == Some(ws_num)))
         .context("no output found")?;
     let ws = output
         .iter()
-        .find(|n| n.is_workspace() && n.num.unwrap() == ws_num)
+        .find(|n| n.is_workspace() && n.num == Some(ws_num))
         .context("no workspace found")?;
     let focused_workspace = workspaces
         .iter()
@@ -82,12 +79,12 @@ where
         PERSWAY_TMP_WORKSPACE, output.id
     ));
     log::debug!("relayout before layout closure: {}", cmd);
-    connection.run_command(cmd).await?;
+    commands.run_command(cmd).await?;
     task::sleep(Duration::from_millis(50)).await;
-    let closure_conn = Connection::new().await?;
-    f(closure_conn, ws_num, ws.id, output.id, windows).await?;
+    let closure_commands = CommandRunner::new(mode).await?;
+    f(closure_commands, ws_num, ws.id, output.id, windows).await?;
     task::sleep(Duration::from_millis(50)).await;
-    let workspaces = connection.get_workspaces().await?;
+    let workspaces = commands.connection().get_workspaces().await?;
     let focused_workspace_after_closure = workspaces
         .iter()
         .find(|w| w.focused)
@@ -100,14 +97,25 @@ where
             output_id = output.id,
         ));
     }
-    cmd.push_str(&format!(
-        "rename workspace to {ws_name}",
-        ws_name = focused_workspace.name
-    ));
+    // the workspace was recreated under its bare number
+    let current_name = workspaces
+        .iter()
+        .find(|w| w.num == focused_workspace.num)
+        .map(|w| w.name.clone())
+        .unwrap_or_else(|| focused_workspace.num.to_string());
+    if current_name != focused_workspace.name {
+        cmd.push_str(&format!(
+            "rename workspace {} to {}",
+            quote(&current_name),
+            quote(&focused_workspace.name)
+        ));
+    }
     log::debug!(
         "rename new workspace to old name after layout closure: {}",
         cmd
     );
-    connection.run_command(cmd).await?;
+    if !cmd.is_empty() {
+        commands.run_command(cmd).await?;
+    }
     Ok(())
 }
@@ -0,0 +1,1585 @@
+//! A stand-in for sway's IPC socket. It keeps a small model of the tree,
+//! implements the subset of commands persway sends and emits window events to
+//! subscribers, either in response to commands or when scripted by a test.
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+const GET_MARKS: u32 = 5;
+const GET_VERSION: u32 = 7;
+const WINDOW_EVENT: u32 = 0x8000_0003;
+
+const OUTPUT_NAME: &str = "FAKE-1";
+const OUTPUT_WIDTH: i32 = 1920;
+const OUTPUT_HEIGHT: i32 = 1080;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Root,
+    Output,
+    Workspace,
+    Con,
+    FloatingCon,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    SplitH,
+    SplitV,
+    Stacked,
+    Tabbed,
+    Output,
+    None,
+}
+
+impl Layout {
+    fn as_str(self) -> &'static str {
+        match self {
+            Layout::SplitH => "splith",
+            Layout::SplitV => "splitv",
+            Layout::Stacked => "stacked",
+            Layout::Tabbed => "tabbed",
+            Layout::Output => "output",
+            Layout::None => "none",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "left" => Some(Self::Left),
+            "right" => Some(Self::Right),
+            "up" => Some(Self::Up),
+            "down" => Some(Self::Down),
+            _ => None,
+        }
+    }
+
+    fn is_horizontal(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+
+    fn offset(self) -> i64 {
+        match self {
+            Self::Left | Self::Up => -1,
+            Self::Right | Self::Down => 1,
+        }
+    }
+
+    fn is_parallel(self, layout: Layout) -> bool {
+        match layout {
+            Layout::SplitH | Layout::Tabbed => self.is_horizontal(),
+            Layout::SplitV | Layout::Stacked => !self.is_horizontal(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Con {
+    pub id: i64,
+    pub parent: Option<i64>,
+    pub kind: Kind,
+    pub name: Option<String>,
+    pub layout: Layout,
+    pub percent: f64,
+    pub rect: Rect,
+    pub app_id: Option<String>,
+    pub pid: Option<i32>,
+    pub marks: Vec<String>,
+    pub nodes: Vec<i64>,
+    pub floating_nodes: Vec<i64>,
+    pub focus: Vec<i64>,
+    pub urgent: bool,
+    pub fullscreen: bool,
+    // when the node or one of its descendants was focused last, orders the
+    // focus lists like sway's seat focus stack does
+    focused_at: u64,
+}
+
+impl Con {
+    fn new(id: i64, kind: Kind, layout: Layout) -> Self {
+        Con {
+            id,
+            parent: None,
+            kind,
+            name: None,
+            layout,
+            percent: 1.0,
+            rect: Rect::default(),
+            app_id: None,
+            pid: None,
+            marks: Vec::new(),
+            nodes: Vec::new(),
+            floating_nodes: Vec::new(),
+            focus: Vec::new(),
+            urgent: false,
+            fullscreen: false,
+            focused_at: 0,
+        }
+    }
+
+    pub fn is_view(&self) -> bool {
+        self.pid.is_some()
+    }
+
+    fn num(&self) -> i32 {
+        workspace_num(self.name.as_deref().unwrap_or_default())
+    }
+}
+
+fn workspace_num(name: &str) -> i32 {
+    let digits: String = name.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().unwrap_or(-1)
+}
+
+/// The tree model. Every mutation that sway would report queues a window
+/// event which the server sends to subscribers.
+pub struct Tree {
+    pub cons: BTreeMap<i64, Con>,
+    pub focused: i64,
+    root: i64,
+    output: i64,
+    next_id: i64,
+    focus_clock: u64,
+    events: Vec<Value>,
+}
+
+enum Criteria {
+    ConId(i64),
+    ConMark(String),
+    AppId(String),
+    Tiling,
+    Floating,
+}
+
+impl Tree {
+    fn new() -> Self {
+        let mut tree = Tree {
+            cons: BTreeMap::new(),
+            focused: 0,
+            root: 1,
+            output: 2,
+            next_id: 3,
+            focus_clock: 0,
+            events: Vec::new(),
+        };
+        let mut root = Con::new(1, Kind::Root, Layout::SplitH);
+        root.name = Some(String::from("root"));
+        root.nodes.push(2);
+        let mut output = Con::new(2, Kind::Output, Layout::Output);
+        output.name = Some(String::from(OUTPUT_NAME));
+        output.parent = Some(1);
+        tree.cons.insert(1, root);
+        tree.cons.insert(2, output);
+        let ws = tree.create_workspace("1");
+        tree.focused = ws;
+        tree.arrange();
+        tree
+    }
+
+    fn alloc_id(&mut self) -> i64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn con(&self, id: i64) -> &Con {
+        &self.cons[&id]
+    }
+
+    fn con_mut(&mut self, id: i64) -> &mut Con {
+        self.cons.get_mut(&id).unwrap()
+    }
+
+    fn parent(&self, id: i64) -> i64 {
+        self.con(id).parent.unwrap()
+    }
+
+    pub fn workspace_of(&self, id: i64) -> Option<i64> {
+        let mut current = id;
+        loop {
+            let con = self.cons.get(&current)?;
+            if con.kind == Kind::Workspace {
+                return Some(current);
+            }
+            current = con.parent?;
+        }
+    }
+
+    pub fn focused_workspace(&self) -> i64 {
+        self.workspace_of(self.focused).unwrap()
+    }
+
+    pub fn workspace_by_name(&self, name: &str) -> Option<i64> {
+        self.con(self.output)
+            .nodes
+            .iter()
+            .copied()
+            .find(|&ws| self.con(ws).name.as_deref() == Some(name))
+    }
+
+    pub fn workspace_by_num(&self, num: i32) -> Option<i64> {
+        self.con(self.output)
+            .nodes
+            .iter()
+            .copied()
+            .find(|&ws| self.con(ws).num() == num)
+    }
+
+    fn create_workspace(&mut self, name: &str) -> i64 {
+        let id = self.alloc_id();
+        let mut ws = Con::new(id, Kind::Workspace, Layout::SplitH);
+        ws.name = Some(name.to_string());
+        ws.parent = Some(self.output);
+        self.cons.insert(id, ws);
+        let output = self.output;
+        let num = workspace_num(name);
+        let index = self
+            .con(output)
+            .nodes
+            .iter()
+            .position(|&w| {
+                let other = self.con(w).num();
+                num != -1 && (other == -1 || other > num)
+            })
+            .unwrap_or(self.con(output).nodes.len());
+        self.con_mut(output).nodes.insert(index, id);
+        self.con_mut(output).focus.push(id);
+        id
+    }
+
+    fn consider_destroy_workspace(&mut self, ws: i64) {
+        let con = self.con(ws);
+        if con.kind == Kind::Workspace
+            && con.nodes.is_empty()
+            && con.floating_nodes.is_empty()
+            && self.focused_workspace() != ws
+        {
+            let output = self.output;
+            self.con_mut(output).nodes.retain(|&w| w != ws);
+            self.con_mut(output).focus.retain(|&w| w != ws);
+            self.cons.remove(&ws);
+        }
+    }
+
+    /// Moves the node to the front of the focus stacks of its ancestors
+    /// without changing which node has focus.
+    fn promote(&mut self, id: i64) {
+        self.focus_clock += 1;
+        self.con_mut(id).focused_at = self.focus_clock;
+        let mut current = id;
+        while let Some(parent) = self.con(current).parent {
+            self.con_mut(parent).focused_at = self.focus_clock;
+            let focus = &mut self.con_mut(parent).focus;
+            focus.retain(|&c| c != current);
+            focus.insert(0, current);
+            current = parent;
+        }
+    }
+
+    fn is_descendant(&self, id: i64, ancestor: i64) -> bool {
+        let mut current = Some(id);
+        while let Some(c) = current {
+            if c == ancestor {
+                return true;
+            }
+            current = self.cons.get(&c).and_then(|c| c.parent);
+        }
+        false
+    }
+
+    /// Keeps focus on the moved node, as sway does.
+    fn refocus_moved(&mut self, id: i64) {
+        if self.is_descendant(self.focused, id) {
+            self.promote(self.focused);
+        }
+    }
+
+    fn set_focus(&mut self, id: i64) {
+        let old = self.focused;
+        let old_ws = self.focused_workspace();
+        self.promote(id);
+        self.focused = id;
+        if old_ws != self.focused_workspace() && self.cons.contains_key(&old_ws) {
+            self.consider_destroy_workspace(old_ws);
+        }
+        if old != id && self.con(id).is_view() {
+            self.queue_event("focus", id);
+        }
+    }
+
+    /// Follows the focus stack down from the given node to a leaf.
+    fn focus_inactive(&self, id: i64) -> i64 {
+        let mut current = id;
+        loop {
+            let con = self.con(current);
+            let next = con
+                .focus
+                .first()
+                .or_else(|| con.nodes.first())
+                .or_else(|| con.floating_nodes.first());
+            match next {
+                Some(&next) => current = next,
+                None => return current,
+            }
+        }
+    }
+
+    fn focus_inactive_tiling(&self, ws: i64) -> Option<i64> {
+        let con = self.con(ws);
+        let mut current = *con
+            .focus
+            .iter()
+            .find(|c| con.nodes.contains(c))
+            .or_else(|| con.nodes.first())?;
+        loop {
+            let con = self.con(current);
+            match con.focus.first().or_else(|| con.nodes.first()) {
+                Some(&next) => current = next,
+                None => return Some(current),
+            }
+        }
+    }
+
+    fn siblings(&self, id: i64) -> &Vec<i64> {
+        let parent = self.con(self.parent(id));
+        if parent.floating_nodes.contains(&id) {
+            &parent.floating_nodes
+        } else {
+            &parent.nodes
+        }
+    }
+
+    fn index_of(&self, id: i64) -> usize {
+        self.siblings(id).iter().position(|&c| c == id).unwrap()
+    }
+
+    fn normalize(&mut self, parent: i64) {
+        let nodes = self.con(parent).nodes.clone();
+        let total: f64 = nodes.iter().map(|&c| self.con(c).percent).sum();
+        if total > 0.0 {
+            for c in nodes {
+                self.con_mut(c).percent /= total;
+            }
+        }
+    }
+
+    /// Removes the node from its parent. Empty containers left behind are
+    /// not reaped, see `reap`.
+    fn detach(&mut self, id: i64) -> Option<i64> {
+        let parent = self.con(id).parent?;
+        let p = self.con_mut(parent);
+        p.nodes.retain(|&c| c != id);
+        p.floating_nodes.retain(|&c| c != id);
+        p.focus.retain(|&c| c != id);
+        self.con_mut(id).parent = None;
+        self.normalize(parent);
+        Some(parent)
+    }
+
+    fn reap(&mut self, id: i64) {
+        let mut current = id;
+        while let Some(con) = self.cons.get(&current) {
+            if con.kind != Kind::Con || con.is_view() || !con.nodes.is_empty() {
+                return;
+            }
+            let parent = self.detach(current);
+            self.cons.remove(&current);
+            match parent {
+                Some(parent) => current = parent,
+                None => return,
+            }
+        }
+    }
+
+    fn insert_child(&mut self, parent: i64, id: i64, index: usize) {
+        let n = self.con(parent).nodes.len() as f64;
+        for c in self.con(parent).nodes.clone() {
+            self.con_mut(c).percent *= n / (n + 1.0);
+        }
+        let index = index.min(self.con(parent).nodes.len());
+        self.con_mut(parent).nodes.insert(index, id);
+        let focused_at = self.con(id).focused_at;
+        let position = self
+            .con(parent)
+            .focus
+            .iter()
+            .position(|&c| self.con(c).focused_at < focused_at)
+            .unwrap_or(self.con(parent).focus.len());
+        self.con_mut(parent).focus.insert(position, id);
+        let con = self.con_mut(id);
+        con.parent = Some(parent);
+        con.percent = 1.0 / (n + 1.0);
+        if con.kind == Kind::FloatingCon {
+            con.kind = Kind::Con;
+        }
+    }
+
+    fn add_sibling(&mut self, sibling: i64, id: i64, offset: usize) {
+        let parent = self.parent(sibling);
+        let index = self.index_of(sibling) + offset;
+        self.insert_child(parent, id, index);
+    }
+
+    fn replace(&mut self, old: i64, new: i64) {
+        let parent = self.parent(old);
+        let index = self.index_of(old);
+        let percent = self.con(old).percent;
+        let p = self.con_mut(parent);
+        p.nodes[index] = new;
+        for f in p.focus.iter_mut() {
+            if *f == old {
+                *f = new;
+            }
+        }
+        self.con_mut(new).parent = Some(parent);
+        self.con_mut(new).percent = percent;
+        self.con_mut(old).parent = None;
+    }
+
+    fn new_container(&mut self, layout: Layout) -> i64 {
+        let id = self.alloc_id();
+        self.cons.insert(id, Con::new(id, Kind::Con, layout));
+        id
+    }
+
+    fn split(&mut self, id: i64, layout: Layout) {
+        let parent = self.parent(id);
+        let parent_layout = self.con(parent).layout;
+        if self.con(id).kind == Kind::FloatingCon {
+            return;
+        }
+        if self.con(parent).nodes.len() == 1
+            && matches!(parent_layout, Layout::SplitH | Layout::SplitV)
+        {
+            self.con_mut(parent).layout = layout;
+            return;
+        }
+        let container = self.new_container(layout);
+        self.con_mut(container).focused_at = self.con(id).focused_at;
+        self.replace(id, container);
+        self.con_mut(container).nodes.push(id);
+        self.con_mut(container).focus.push(id);
+        self.con_mut(id).parent = Some(container);
+        self.con_mut(id).percent = 1.0;
+    }
+
+    pub fn new_window(&mut self, app_id: &str) -> i64 {
+        let id = self.new_window_in_background(app_id);
+        self.set_focus(id);
+        id
+    }
+
+    pub fn new_window_in_background(&mut self, app_id: &str) -> i64 {
+        let id = self.alloc_id();
+        let mut con = Con::new(id, Kind::Con, Layout::None);
+        con.name = Some(app_id.to_string());
+        con.app_id = Some(app_id.to_string());
+        con.pid = Some(1000 + id as i32);
+        self.cons.insert(id, con);
+        let focused = self.focused;
+        let ws = self.focused_workspace();
+        if focused != ws && self.con(focused).kind == Kind::Con {
+            self.add_sibling(focused, id, 1);
+        } else if let Some(sibling) = self.focus_inactive_tiling(ws) {
+            self.add_sibling(sibling, id, 1);
+        } else {
+            self.insert_child(ws, id, usize::MAX);
+        }
+        self.queue_event("new", id);
+        id
+    }
+
+    pub fn close_window(&mut self, id: i64) {
+        let was_focused = self.focused == id;
+        let ws = self.workspace_of(id).unwrap();
+        self.queue_event("close", id);
+        let parent = self.detach(id).unwrap();
+        self.cons.remove(&id);
+        self.reap(parent);
+        if was_focused {
+            let next = if self.cons.contains_key(&parent) {
+                self.focus_inactive(parent)
+            } else {
+                self.focus_inactive(ws)
+            };
+            self.focused = ws;
+            self.set_focus(next);
+        }
+    }
+
+    pub fn set_urgent(&mut self, id: i64, urgent: bool) {
+        self.con_mut(id).urgent = urgent;
+        self.queue_event("urgent", id);
+    }
+
+    fn move_to_workspace(&mut self, id: i64, ws: i64) {
+        if self.workspace_of(id) == Some(ws) {
+            return;
+        }
+        let was_focused = self.focused == id;
+        let old_ws = self.workspace_of(id).unwrap();
+        if self.con(id).kind == Kind::FloatingCon {
+            self.detach(id);
+            self.con_mut(ws).floating_nodes.push(id);
+            self.con_mut(id).parent = Some(ws);
+        } else {
+            let old_parent = self.detach(id).unwrap();
+            self.reap(old_parent);
+            match self.focus_inactive_tiling(ws) {
+                Some(sibling) => self.add_sibling(sibling, id, 1),
+                None => self.insert_child(ws, id, usize::MAX),
+            }
+        }
+        self.promote(id);
+        if was_focused {
+            let next = self.focus_inactive(old_ws);
+            self.focused = old_ws;
+            self.set_focus(next);
+        }
+        self.queue_event("move", id);
+        self.consider_destroy_workspace(old_ws);
+    }
+
+    fn move_to_container(&mut self, id: i64, destination: i64) {
+        if self.is_descendant(destination, id) {
+            return;
+        }
+        let old_parent = self.detach(id).unwrap();
+        if self.con(destination).is_view() {
+            self.add_sibling(destination, id, 1);
+        } else {
+            self.insert_child(destination, id, usize::MAX);
+        }
+        self.reap(old_parent);
+        self.refocus_moved(id);
+        self.queue_event("move", id);
+    }
+
+    fn move_to_container_from_direction(&mut self, id: i64, destination: i64, dir: Direction) {
+        if self.con(destination).is_view() {
+            if self.parent(destination) == self.parent(id) {
+                let parent = self.parent(id);
+                let a = self.index_of(id);
+                let b = self.index_of(destination);
+                self.con_mut(parent).nodes.swap(a, b);
+            } else {
+                let offset = usize::from(matches!(dir, Direction::Left | Direction::Up));
+                self.detach(id);
+                self.add_sibling(destination, id, offset);
+            }
+            return;
+        }
+        if dir.is_parallel(self.con(destination).layout) {
+            let index = match dir {
+                Direction::Right | Direction::Down => 0,
+                _ => usize::MAX,
+            };
+            self.detach(id);
+            self.insert_child(destination, id, index);
+            return;
+        }
+        let con = self.con(destination);
+        match con.focus.first().or_else(|| con.nodes.first()).copied() {
+            Some(child) => self.move_to_container_from_direction(id, child, dir),
+            None => {
+                self.detach(id);
+                self.insert_child(destination, id, usize::MAX);
+            }
+        }
+    }
+
+    fn move_in_direction(&mut self, id: i64, dir: Direction) -> bool {
+        if self.con(id).kind == Kind::FloatingCon {
+            return false;
+        }
+        let ws = self.workspace_of(id).unwrap();
+        let container_parent = self.parent(id);
+        let mut current = id;
+        loop {
+            let parent = self.parent(current);
+            let layout = self.con(parent).layout;
+            let index = self.index_of(current) as i64;
+            let len = self.con(parent).nodes.len() as i64;
+            let desired = index + dir.offset();
+            if dir.is_parallel(layout) {
+                if desired < 0 || desired >= len {
+                    if parent == container_parent {
+                        if parent == ws {
+                            break;
+                        }
+                        current = parent;
+                        continue;
+                    }
+                    self.detach(id);
+                    let index = self.index_of(current) + usize::from(dir.offset() > 0);
+                    self.insert_child(parent, id, index);
+                    return true;
+                }
+                let destination = self.con(parent).nodes[desired as usize];
+                self.move_to_container_from_direction(id, destination, dir);
+                return true;
+            }
+            if parent == ws {
+                break;
+            }
+            current = parent;
+        }
+        let ws_layout = self.con(ws).layout;
+        if !dir.is_parallel(ws_layout) || matches!(ws_layout, Layout::Tabbed | Layout::Stacked) {
+            self.rejigger_workspace(ws, id, dir);
+            return true;
+        }
+        false
+    }
+
+    fn rejigger_workspace(&mut self, ws: i64, id: i64, dir: Direction) {
+        let layout = if dir.is_horizontal() {
+            Layout::SplitH
+        } else {
+            Layout::SplitV
+        };
+        if self.parent(id) == ws && self.con(ws).nodes.len() == 1 {
+            self.con_mut(ws).layout = layout;
+            return;
+        }
+        self.detach(id);
+        let wrapper = self.new_container(self.con(ws).layout);
+        let children = std::mem::take(&mut self.con_mut(ws).nodes);
+        let focus: Vec<i64> = self.con(ws).focus.clone();
+        for &c in &children {
+            self.con_mut(c).parent = Some(wrapper);
+        }
+        self.con_mut(wrapper).focus = focus
+            .iter()
+            .copied()
+            .filter(|c| children.contains(c))
+            .collect();
+        self.con_mut(wrapper).nodes = children.clone();
+        self.con_mut(ws).focus.retain(|c| !children.contains(c));
+        self.con_mut(ws).nodes.push(wrapper);
+        self.con_mut(ws).focus.push(wrapper);
+        self.con_mut(wrapper).parent = Some(ws);
+        self.con_mut(wrapper).percent = 1.0;
+        let index = usize::from(matches!(dir, Direction::Right | Direction::Down));
+        self.insert_child(ws, id, index);
+        self.flatten(wrapper);
+        self.con_mut(ws).layout = layout;
+    }
+
+    fn flatten(&mut self, id: i64) {
+        let mut current = id;
+        loop {
+            let con = self.con(current);
+            if con.is_view() || con.nodes.len() != 1 {
+                return;
+            }
+            let child = con.nodes[0];
+            self.con_mut(current).nodes.clear();
+            self.replace(current, child);
+            self.cons.remove(&current);
+            current = child;
+        }
+    }
+
+    fn swap(&mut self, a: i64, b: i64) {
+        if a == b {
+            return;
+        }
+        let pa = self.parent(a);
+        let pb = self.parent(b);
+        let ia = self.index_of(a);
+        let ib = self.index_of(b);
+        let (pa_percent, pb_percent) = (self.con(a).percent, self.con(b).percent);
+        self.con_mut(pa).nodes[ia] = b;
+        self.con_mut(pb).nodes[ib] = a;
+        for (parent, old, new) in [(pa, a, b), (pb, b, a)] {
+            for f in self.con_mut(parent).focus.iter_mut() {
+                if *f == old {
+                    *f = new;
+                }
+            }
+        }
+        if pa == pb {
+            // both ids are in the focus list, the loop above swapped twice
+            let focus = &mut self.con_mut(pa).focus;
+            for f in focus.iter_mut() {
+                if *f == a {
+                    *f = b;
+                } else if *f == b {
+                    *f = a;
+                }
+            }
+        }
+        self.con_mut(a).parent = Some(pb);
+        self.con_mut(b).parent = Some(pa);
+        self.con_mut(a).percent = pb_percent;
+        self.con_mut(b).percent = pa_percent;
+    }
+
+    /// Moves the given edge of the node, taking the space from or giving it
+    /// to the sibling on that side only.
+    fn resize_edge(&mut self, id: i64, dir: Direction, amount: f64, px: bool) {
+        let wanted = if dir.is_horizontal() {
+            Layout::SplitH
+        } else {
+            Layout::SplitV
+        };
+        let mut current = id;
+        loop {
+            let Some(parent) = self.con(current).parent else {
+                return;
+            };
+            let p = self.con(parent);
+            if p.kind == Kind::Output {
+                return;
+            }
+            if p.layout == wanted && p.nodes.len() > 1 {
+                break;
+            }
+            current = parent;
+        }
+        let parent = self.parent(current);
+        let index = self.index_of(current) as i64 + dir.offset();
+        let nodes = &self.con(parent).nodes;
+        if index < 0 || index as usize >= nodes.len() {
+            return;
+        }
+        let sibling = nodes[index as usize];
+        let rect = self.con(parent).rect;
+        let extent = if dir.is_horizontal() {
+            rect.width
+        } else {
+            rect.height
+        };
+        let total: f64 = nodes.iter().map(|&c| self.con(c).percent).sum();
+        let fraction = if px {
+            amount / extent as f64 * total
+        } else {
+            amount / 100.0 * total
+        };
+        self.con_mut(current).percent += fraction;
+        self.con_mut(sibling).percent -= fraction;
+    }
+
+    fn resize_set(&mut self, id: i64, horizontal: bool, ppt: f64) {
+        let wanted = if horizontal {
+            Layout::SplitH
+        } else {
+            Layout::SplitV
+        };
+        let mut current = id;
+        loop {
+            let Some(parent) = self.con(current).parent else {
+                return;
+            };
+            let p = self.con(parent);
+            if p.kind == Kind::Output {
+                return;
+            }
+            if p.layout == wanted && p.nodes.len() > 1 {
+                break;
+            }
+            current = parent;
+        }
+        let parent = self.parent(current);
+        let fraction = (ppt / 100.0).clamp(0.05, 0.95);
+        let others: Vec<i64> = self
+            .con(parent)
+            .nodes
+            .iter()
+            .copied()
+            .filter(|&c| c != current)
+            .collect();
+        let rest: f64 = others.iter().map(|&c| self.con(c).percent).sum();
+        for c in others {
+            let share = if rest > 0.0 {
+                self.con(c).percent / rest
+            } else {
+                1.0
+            };
+            self.con_mut(c).percent = share * (1.0 - fraction);
+        }
+        self.con_mut(current).percent = fraction;
+    }
+
+    fn set_floating(&mut self, id: i64, floating: bool) {
+        let is_floating = self.con(id).kind == Kind::FloatingCon;
+        if floating == is_floating || !self.con(id).is_view() {
+            return;
+        }
+        let ws = self.workspace_of(id).unwrap();
+        if floating {
+            let old_parent = self.detach(id).unwrap();
+            self.reap(old_parent);
+            self.con_mut(ws).floating_nodes.push(id);
+            self.con_mut(ws).focus.push(id);
+            let con = self.con_mut(id);
+            con.parent = Some(ws);
+            con.kind = Kind::FloatingCon;
+        } else {
+            self.detach(id);
+            self.con_mut(id).kind = Kind::Con;
+            match self.focus_inactive_tiling(ws) {
+                Some(sibling) => self.add_sibling(sibling, id, 1),
+                None => self.insert_child(ws, id, usize::MAX),
+            }
+        }
+        self.refocus_moved(id);
+        self.queue_event("floating", id);
+    }
+
+    fn focus_workspace(&mut self, ws: i64) {
+        let target = self.focus_inactive(ws);
+        self.set_focus(target);
+    }
+
+    fn queue_event(&mut self, change: &str, id: i64) {
+        self.arrange();
+        self.events.push(json!({
+            "change": change,
+            "container": self.node_json(id),
+        }));
+    }
+
+    fn matches(&self, id: i64, criteria: &[Criteria]) -> bool {
+        let con = self.con(id);
+        criteria.iter().all(|c| match c {
+            Criteria::ConId(cid) => *cid == id,
+            Criteria::ConMark(mark) => con.marks.contains(mark),
+            Criteria::AppId(app_id) => con.app_id.as_deref() == Some(app_id.as_str()),
+            Criteria::Tiling => con.is_view() && con.kind == Kind::Con,
+            Criteria::Floating => con.is_view() && con.kind == Kind::FloatingCon,
+        })
+    }
+
+    /// Runs a sway command string, returning one outcome per command.
+    pub fn run_command(&mut self, payload: &str) -> Vec<Result<(), String>> {
+        let mut outcomes = Vec::new();
+        for statement in split_unquoted(payload, ';') {
+            let mut criteria: Option<Vec<Criteria>> = None;
+            for (i, part) in split_unquoted(statement, ',').into_iter().enumerate() {
+                let mut part = part.trim();
+                if i == 0 && part.starts_with('[') {
+                    let Some(end) = part.find(']') else {
+                        outcomes.push(Err(String::from("unterminated criteria")));
+                        break;
+                    };
+                    match parse_criteria(&part[1..end]) {
+                        Ok(c) => criteria = Some(c),
+                        Err(e) => {
+                            outcomes.push(Err(e));
+                            break;
+                        }
+                    }
+                    part = part[end + 1..].trim();
+                }
+                if part.is_empty() {
+                    continue;
+                }
+                let targets: Vec<i64> = match &criteria {
+                    Some(criteria) => self
+                        .cons
+                        .keys()
+                        .copied()
+                        .filter(|&id| {
+                            !matches!(
+                                self.con(id).kind,
+                                Kind::Root | Kind::Output | Kind::Workspace
+                            ) && self.matches(id, criteria)
+                        })
+                        .collect(),
+                    None => vec![self.focused],
+                };
+                let mut outcome = Ok(());
+                for target in targets {
+                    if !self.cons.contains_key(&target) {
+                        continue;
+                    }
+                    if let Err(e) = self.execute(part, target, criteria.is_some()) {
+                        outcome = Err(e);
+                    }
+                }
+                outcomes.push(outcome);
+            }
+        }
+        self.arrange();
+        outcomes
+    }
+
+    fn execute(&mut self, command: &str, target: i64, with_criteria: bool) -> Result<(), String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let is_workspace = self.con(target).kind == Kind::Workspace;
+        match words.as_slice() {
+            ["focus"] => {
+                if with_criteria {
+                    self.set_focus(target);
+                }
+            }
+            ["split", dir] | [dir @ ("splitv" | "splith")] => {
+                let layout = match *dir {
+                    "v" | "vertical" | "splitv" => Layout::SplitV,
+                    "h" | "horizontal" | "splith" => Layout::SplitH,
+                    _ => return Err(format!("unknown split: {}", dir)),
+                };
+                if is_workspace {
+                    self.con_mut(target).layout = layout;
+                } else {
+                    self.split(target, layout);
+                }
+            }
+            ["layout", layout] => {
+                let layout = match *layout {
+                    "tabbed" => Layout::Tabbed,
+                    "stacking" | "stacked" => Layout::Stacked,
+                    "splith" => Layout::SplitH,
+                    "splitv" => Layout::SplitV,
+                    _ => return Err(format!("unknown layout: {}", layout)),
+                };
+                let container = if is_workspace {
+                    target
+                } else {
+                    self.parent(target)
+                };
+                self.con_mut(container).layout = layout;
+            }
+            ["mark", "--add", mark] | ["mark", mark] => {
+                let mark = mark.to_string();
+                for con in self.cons.values_mut() {
+                    con.marks.retain(|m| *m != mark);
+                }
+                self.con_mut(target).marks.push(mark);
+            }
+            ["unmark", mark] => {
+                self.con_mut(target).marks.retain(|m| m != mark);
+            }
+            ["move", "container" | "window", "to", "mark", mark] | ["move", "to", "mark", mark] => {
+                let destination = self
+                    .cons
+                    .values()
+                    .find(|c| c.marks.iter().any(|m| m == mark))
+                    .map(|c| c.id)
+                    .ok_or_else(|| format!("mark {} not found", mark))?;
+                self.move_to_container(target, destination);
+            }
+            ["move", "workspace", "to", "output", ..] => {}
+            ["move", rest @ ..] if rest.contains(&"workspace") => {
+                let pos = rest.iter().position(|w| *w == "workspace").unwrap();
+                let ws = match &rest[pos + 1..] {
+                    ["number", num, ..] => {
+                        let num: i32 = num.parse().map_err(|_| "invalid number".to_string())?;
+                        match self.workspace_by_num(num) {
+                            Some(ws) => ws,
+                            None => self.create_workspace(&num.to_string()),
+                        }
+                    }
+                    name => {
+                        let name = name.join(" ");
+                        match self.workspace_by_name(&name) {
+                            Some(ws) => ws,
+                            None => self.create_workspace(&name),
+                        }
+                    }
+                };
+                self.move_to_workspace(target, ws);
+            }
+            ["move", dir] => {
+                let dir =
+                    Direction::parse(dir).ok_or_else(|| format!("unknown direction {}", dir))?;
+                let old_parent = self.parent(target);
+                if self.move_in_direction(target, dir) {
+                    self.reap(old_parent);
+                    self.refocus_moved(target);
+                    self.queue_event("move", target);
+                }
+            }
+            ["swap", "container", "with", "con_id", id] => {
+                let other: i64 = id.parse().map_err(|_| "invalid con_id".to_string())?;
+                if !self.cons.contains_key(&other) {
+                    return Err(format!("no container with id {}", other));
+                }
+                self.swap(target, other);
+            }
+            ["resize", change @ ("grow" | "shrink"), dir, amount, unit @ ..] => {
+                let dir =
+                    Direction::parse(dir).ok_or_else(|| format!("unknown direction {}", dir))?;
+                let mut amount: f64 = amount.parse().map_err(|_| "invalid amount".to_string())?;
+                if *change == "shrink" {
+                    amount = -amount;
+                }
+                self.resize_edge(target, dir, amount, unit == ["px"]);
+            }
+            ["resize", "set", axis @ ("width" | "height"), amount, ..] => {
+                let amount: f64 = amount.parse().map_err(|_| "invalid amount".to_string())?;
+                self.resize_set(target, *axis == "width", amount);
+            }
+            ["workspace", "number", num, ..] => {
+                let num: i32 = num.parse().map_err(|_| "invalid number".to_string())?;
+                let ws = match self.workspace_by_num(num) {
+                    Some(ws) => ws,
+                    None => self.create_workspace(&num.to_string()),
+                };
+                self.focus_workspace(ws);
+            }
+            ["workspace", name @ ..] => {
+                let name = name.join(" ");
+                let ws = match self.workspace_by_name(&name) {
+                    Some(ws) => ws,
+                    None => self.create_workspace(&name),
+                };
+                self.focus_workspace(ws);
+            }
+            ["rename", "workspace", rest @ ..] => {
+                let pos = rest
+                    .iter()
+                    .position(|w| *w == "to")
+                    .ok_or_else(|| String::from("missing to"))?;
+                let ws = if pos == 0 {
+                    self.focused_workspace()
+                } else {
+                    let old = unquote(&rest[..pos].join(" "));
+                    self.workspace_by_name(&old)
+                        .ok_or_else(|| format!("no workspace named {}", old))?
+                };
+                let new_name = unquote(&rest[pos + 1..].join(" "));
+                if self
+                    .workspace_by_name(&new_name)
+                    .is_some_and(|other| other != ws)
+                {
+                    return Err(format!("workspace {} already exists", new_name));
+                }
+                self.con_mut(ws).name = Some(new_name);
+            }
+            ["floating", state] => {
+                let floating = match *state {
+                    "enable" => true,
+                    "disable" => false,
+                    "toggle" => self.con(target).kind != Kind::FloatingCon,
+                    _ => return Err(format!("unknown floating state {}", state)),
+                };
+                self.set_floating(target, floating);
+            }
+            ["fullscreen", ..] => {
+                let con = self.con_mut(target);
+                con.fullscreen = !con.fullscreen;
+                self.queue_event("fullscreen_mode", target);
+            }
+            ["kill"] => {
+                if self.con(target).is_view() {
+                    self.close_window(target);
+                }
+            }
+            ["nop", ..] | ["opacity", ..] | ["inhibit_idle", ..] | ["border", ..] => {}
+            _ => return Err(format!("unknown command: {}", command)),
+        }
+        Ok(())
+    }
+
+    fn arrange(&mut self) {
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: OUTPUT_WIDTH,
+            height: OUTPUT_HEIGHT,
+        };
+        let root = self.root;
+        self.con_mut(root).rect = rect;
+        let output = self.output;
+        self.con_mut(output).rect = rect;
+        for ws in self.con(output).nodes.clone() {
+            self.arrange_con(ws, rect);
+        }
+    }
+
+    fn arrange_con(&mut self, id: i64, rect: Rect) {
+        self.con_mut(id).rect = rect;
+        let con = self.con(id);
+        let layout = con.layout;
+        let nodes = con.nodes.clone();
+        let total: f64 = nodes.iter().map(|&c| self.con(c).percent).sum();
+        let mut offset = 0.0;
+        for c in nodes {
+            let share = if total > 0.0 {
+                self.con(c).percent / total
+            } else {
+                0.0
+            };
+            let child_rect = match layout {
+                Layout::SplitH => Rect {
+                    x: rect.x + (offset * rect.width as f64) as i32,
+                    y: rect.y,
+                    width: (share * rect.width as f64) as i32,
+                    height: rect.height,
+                },
+                Layout::SplitV => Rect {
+                    x: rect.x,
+                    y: rect.y + (offset * rect.height as f64) as i32,
+                    width: rect.width,
+                    height: (share * rect.height as f64) as i32,
+                },
+                _ => rect,
+            };
+            offset += share;
+            self.arrange_con(c, child_rect);
+        }
+        for c in self.con(id).floating_nodes.clone() {
+            self.arrange_con(
+                c,
+                Rect {
+                    x: rect.x + rect.width / 4,
+                    y: rect.y + rect.height / 4,
+                    width: rect.width / 2,
+                    height: rect.height / 2,
+                },
+            );
+        }
+    }
+
+    fn is_visible(&self, id: i64) -> bool {
+        let Some(ws) = self.workspace_of(id) else {
+            return false;
+        };
+        if ws != self.focused_workspace() {
+            return false;
+        }
+        let mut current = id;
+        while let Some(parent) = self.con(current).parent {
+            let p = self.con(parent);
+            if matches!(p.layout, Layout::Tabbed | Layout::Stacked) {
+                let active = p
+                    .focus
+                    .iter()
+                    .find(|c| p.nodes.contains(c))
+                    .or_else(|| p.nodes.first());
+                if active != Some(&current) {
+                    return false;
+                }
+            }
+            if parent == ws {
+                break;
+            }
+            current = parent;
+        }
+        true
+    }
+
+    fn rect_json(rect: Rect) -> Value {
+        json!({"x": rect.x, "y": rect.y, "width": rect.width, "height": rect.height})
+    }
+
+    pub fn node_json(&self, id: i64) -> Value {
+        let con = self.con(id);
+        let kind = match con.kind {
+            Kind::Root => "root",
+            Kind::Output => "output",
+            Kind::Workspace => "workspace",
+            Kind::Con => "con",
+            Kind::FloatingCon => "floating_con",
+        };
+        let percent = match con.kind {
+            Kind::Root | Kind::Output | Kind::Workspace => Value::Null,
+            _ if con.fullscreen => json!(1.5),
+            _ => json!(con.percent),
+        };
+        let mut node = json!({
+            "id": id,
+            "name": con.name,
+            "type": kind,
+            "border": "none",
+            "current_border_width": 0,
+            "layout": con.layout.as_str(),
+            "percent": percent,
+            "rect": Self::rect_json(con.rect),
+            "window_rect": Self::rect_json(con.rect),
+            "deco_rect": Self::rect_json(Rect::default()),
+            "geometry": Self::rect_json(con.rect),
+            "urgent": con.urgent,
+            "focused": self.focused == id,
+            "focus": con.focus,
+            "nodes": con.nodes.iter().map(|&c| self.node_json(c)).collect::<Vec<_>>(),
+            "floating_nodes": con.floating_nodes.iter().map(|&c| self.node_json(c)).collect::<Vec<_>>(),
+            "sticky": false,
+            "marks": con.marks,
+        });
+        let object = node.as_object_mut().unwrap();
+        if con.kind == Kind::Workspace {
+            object.insert(String::from("num"), json!(con.num()));
+            object.insert(String::from("output"), json!(OUTPUT_NAME));
+            object.insert(
+                String::from("representation"),
+                json!(self.representation(id)),
+            );
+        }
+        if con.is_view() {
+            object.insert(String::from("app_id"), json!(con.app_id));
+            object.insert(String::from("pid"), json!(con.pid));
+            object.insert(String::from("shell"), json!("xdg_shell"));
+            object.insert(String::from("visible"), json!(self.is_visible(id)));
+            object.insert(
+                String::from("fullscreen_mode"),
+                json!(if con.fullscreen { 1 } else { 0 }),
+            );
+        }
+        node
+    }
+
+    pub fn workspaces_json(&self) -> Value {
+        let focused_ws = self.focused_workspace();
+        let workspaces: Vec<Value> = self
+            .con(self.output)
+            .nodes
+            .iter()
+            .map(|&ws| {
+                let con = self.con(ws);
+                json!({
+                    "id": ws,
+                    "num": con.num(),
+                    "name": con.name,
+                    "layout": con.layout.as_str(),
+                    "visible": ws == focused_ws,
+                    "focused": ws == focused_ws,
+                    "urgent": false,
+                    "representation": self.representation(ws),
+                    "rect": Self::rect_json(con.rect),
+                    "output": OUTPUT_NAME,
+                    "focus": con.focus,
+                })
+            })
+            .collect();
+        json!(workspaces)
+    }
+
+    /// A sway style representation of the tiling tree below the node, with
+    /// windows shown by their app_id, eg. `H[foot V[foot firefox]]`.
+    pub fn representation(&self, id: i64) -> String {
+        let con = self.con(id);
+        if con.is_view() {
+            return con.app_id.clone().unwrap_or_default();
+        }
+        let prefix = match con.layout {
+            Layout::SplitH => "H",
+            Layout::SplitV => "V",
+            Layout::Stacked => "S",
+            Layout::Tabbed => "T",
+            _ => "?",
+        };
+        let children: Vec<String> = con.nodes.iter().map(|&c| self.representation(c)).collect();
+        format!("{}[{}]", prefix, children.join(" "))
+    }
+}
+
+/// Splits a command string at the separator where it isn't quoted.
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Strips the quotes around an argument and the escapes within it.
+fn unquote(s: &str) -> String {
+    match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(s) => s.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => s.to_string(),
+    }
+}
+
+fn parse_criteria(s: &str) -> Result<Vec<Criteria>, String> {
+    let mut criteria = Vec::new();
+    for item in s.split_whitespace() {
+        let (key, value) = item.split_once('=').unwrap_or((item, ""));
+        let value = value.trim_matches('"').to_string();
+        criteria.push(match key {
+            "con_id" => Criteria::ConId(value.parse().map_err(|_| "invalid con_id")?),
+            "con_mark" => Criteria::ConMark(value),
+            "app_id" => Criteria::AppId(value),
+            "tiling" => Criteria::Tiling,
+            "floating" => Criteria::Floating,
+            _ => return Err(format!("unsupported criteria {}", key)),
+        });
+    }
+    Ok(criteria)
+}
+
+struct State {
+    tree: Tree,
+    subscribers: Vec<UnixStream>,
+    last_activity: Instant,
+    commands: Vec<String>,
+}
+
+impl State {
+    fn flush_events(&mut self) {
+        let events = std::mem::take(&mut self.tree.events);
+        for event in events {
+            let payload = event.to_string();
+            self.subscribers
+                .retain_mut(|s| write_message(s, WINDOW_EVENT, payload.as_bytes()).is_ok());
+        }
+    }
+}
+
+/// The fake sway server. It listens on a socket in the temp dir until dropped.
+pub struct FakeSway {
+    state: Arc<Mutex<State>>,
+    socket_path: PathBuf,
+}
+
+impl FakeSway {
+    pub fn start(socket_path: &Path) -> Self {
+        let _ = std::fs::remove_file(socket_path);
+        let listener = UnixListener::bind(socket_path).unwrap();
+        let state = Arc::new(Mutex::new(State {
+            tree: Tree::new(),
+            subscribers: Vec::new(),
+            last_activity: Instant::now(),
+            commands: Vec::new(),
+        }));
+        let accept_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { return };
+                let state = Arc::clone(&accept_state);
+                thread::spawn(move || serve(stream, state));
+            }
+        });
+        FakeSway {
+            state,
+            socket_path: socket_path.to_path_buf(),
+        }
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Runs a closure against the tree, then sends any events it caused.
+    pub fn with_tree<T>(&self, f: impl FnOnce(&mut Tree) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        let result = f(&mut state.tree);
+        state.tree.arrange();
+        state.flush_events();
+        state.last_activity = Instant::now();
+        result
+    }
+
+    /// Opens a new window next to the focused one, like a user launching an app.
+    pub fn new_window(&self, app_id: &str) -> i64 {
+        self.with_tree(|tree| tree.new_window(app_id))
+    }
+
+    /// Opens a window without focusing it, like sway does for no_focus rules.
+    pub fn new_window_in_background(&self, app_id: &str) -> i64 {
+        self.with_tree(|tree| tree.new_window_in_background(app_id))
+    }
+
+    pub fn close_window(&self, id: i64) {
+        self.with_tree(|tree| tree.close_window(id))
+    }
+
+    /// Marks the window as asking for attention, or as no longer doing so.
+    pub fn set_urgent(&self, id: i64, urgent: bool) {
+        self.with_tree(|tree| tree.set_urgent(id, urgent))
+    }
+
+    /// Runs a command as if the user had run it through swaymsg or a binding.
+    pub fn command(&self, cmd: &str) {
+        let outcomes = self.with_tree(|tree| tree.run_command(cmd));
+        for outcome in outcomes {
+            outcome.unwrap();
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.state.lock().unwrap().subscribers.len()
+    }
+
+    /// The commands persway has sent so far.
+    pub fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    pub fn focused(&self) -> i64 {
+        self.with_tree(|tree| tree.focused)
+    }
+
+    /// The representation of the workspace with the given number.
+    pub fn workspace(&self, num: i32) -> String {
+        self.with_tree(|tree| {
+            tree.workspace_by_num(num)
+                .map(|ws| tree.representation(ws))
+                .unwrap_or_default()
+        })
+    }
+
+    /// The names of the workspaces in the order sway lists them.
+    pub fn workspace_names(&self) -> Vec<String> {
+        self.with_tree(|tree| {
+            tree.con(tree.output)
+                .nodes
+                .iter()
+                .map(|&ws| tree.con(ws).name.clone().unwrap_or_default())
+                .collect()
+        })
+    }
+
+    /// The width of the given window in percent of the output.
+    pub fn width_percent(&self, id: i64) -> u32 {
+        self.with_tree(|tree| {
+            let con = tree.con(id);
+            (con.rect.width as f64 * 100.0 / OUTPUT_WIDTH as f64).round() as u32
+        })
+    }
+
+    /// The height of the given window in percent of the output.
+    pub fn height_percent(&self, id: i64) -> u32 {
+        self.with_tree(|tree| {
+            let con = tree.con(id);
+            (con.rect.height as f64 * 100.0 / OUTPUT_HEIGHT as f64).round() as u32
+        })
+    }
+
+    /// Waits until neither persway nor the test has talked to the server for
+    /// a little while, so that all events have been handled.
+    pub fn settle(&self) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            thread::sleep(Duration::from_millis(50));
+            let idle = self.state.lock().unwrap().last_activity.elapsed();
+            if idle > Duration::from_millis(400) || Instant::now() > deadline {
+                return;
+            }
+        }
+    }
+}
+
+impl Drop for FakeSway {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+fn write_message(
+    stream: &mut UnixStream,
+    message_type: u32,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let mut message = Vec::with_capacity(14 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload);
+    stream.write_all(&message)
+}
+
+fn read_message(stream: &mut UnixStream) -> std::io::Result<(u32, Vec<u8>)> {
+    let mut header = [0_u8; 14];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0_u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((message_type, payload))
+}
+
+fn serve(mut stream: UnixStream, state: Arc<Mutex<State>>) {
+    while let Ok((message_type, payload)) = read_message(&mut stream) {
+        let mut state = state.lock().unwrap();
+        state.last_activity = Instant::now();
+        let reply = match message_type {
+            RUN_COMMAND => {
+                let cmd = String::from_utf8_lossy(&payload).to_string();
+                state.commands.push(cmd.clone());
+                let outcomes = state.tree.run_command(&cmd);
+                let reply: Vec<Value> = outcomes
+                    .into_iter()
+                    .map(|outcome| match outcome {
+                        Ok(()) => json!({"success": true}),
+                        Err(e) => json!({"success": false, "parse_error": false, "error": e}),
+                    })
+                    .collect();
+                json!(reply)
+            }
+            GET_WORKSPACES => state.tree.workspaces_json(),
+            GET_TREE => {
+                state.tree.arrange();
+                state.tree.node_json(state.tree.root)
+            }
+            GET_MARKS => {
+                let marks: Vec<String> = state
+                    .tree
+                    .cons
+                    .values()
+                    .flat_map(|c| c.marks.clone())
+                    .collect();
+                json!(marks)
+            }
+            GET_VERSION => json!({
+                "major": 1, "minor": 9, "patch": 0,
+                "human_readable": "fake", "loaded_config_file_name": ""
+            }),
+            SUBSCRIBE => {
+                if let Ok(subscriber) = stream.try_clone() {
+                    state.subscribers.push(subscriber);
+                }
+                json!({"success": true})
+            }
+            _ => json!({"success": false}),
+        };
+        if write_message(&mut stream, message_type, reply.to_string().as_bytes()).is_err() {
+            return;
+        }
+        if message_type == SUBSCRIBE {
+            drop(state);
+            // the stream now only carries events, keep it open until the
+            // client goes away
+            let mut buf = [0_u8; 1];
+            let _ = stream.read(&mut buf);
+            return;
+        }
+        state.flush_events();
+    }
+}
@@ -0,0 +1,126 @@
+#![allow(dead_code)]
+
+pub mod fake_sway;
+
+use fake_sway::FakeSway;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static NEXT_SOCKET: AtomicUsize = AtomicUsize::new(0);
+
+pub fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "persway-test-{}-{}-{}.sock",
+        std::process::id(),
+        NEXT_SOCKET.fetch_add(1, Ordering::SeqCst),
+        name
+    ))
+}
+
+/// A persway command talking to the given fake sway server.
+pub fn persway(sway: &FakeSway) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_persway"));
+    command
+        .env("I3SOCK", sway.socket_path())
+        .env("SWAYSOCK", sway.socket_path());
+    command
+}
+
+/// A fake sway server together with a persway daemon talking to it.
+pub struct Session {
+    pub sway: FakeSway,
+    daemon: Child,
+    socket_path: PathBuf,
+}
+
+impl Session {
+    /// Starts a daemon with the given daemon arguments, eg. `["-d", "spiral"]`.
+    pub fn start(daemon_args: &[&str]) -> Self {
+        Self::start_on(FakeSway::start(&socket_path("sway")), daemon_args)
+    }
+
+    /// Starts a daemon against a fake sway that may have windows open already.
+    pub fn start_on(sway: FakeSway, daemon_args: &[&str]) -> Self {
+        let socket_path = socket_path("persway");
+        let daemon = persway(&sway)
+            .arg("-s")
+            .arg(&socket_path)
+            .arg("daemon")
+            .args(daemon_args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        wait_for(&socket_path);
+        let session = Session {
+            sway,
+            daemon,
+            socket_path,
+        };
+        session.sway.settle();
+        session
+    }
+
+    /// A persway client command against the daemon, for commands that keep running.
+    pub fn client(&self, args: &[&str]) -> Command {
+        let mut command = persway(&self.sway);
+        command.arg("-s").arg(&self.socket_path).args(args);
+        command
+    }
+
+    /// Runs a persway client command against the daemon, returning its output.
+    pub fn persway(&self, args: &[&str]) -> String {
+        let output = self.client(args).output().unwrap();
+        self.sway.settle();
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    pub fn new_window(&self, app_id: &str) -> i64 {
+        let id = self.sway.new_window(app_id);
+        self.sway.settle();
+        id
+    }
+
+    pub fn new_window_in_background(&self, app_id: &str) -> i64 {
+        let id = self.sway.new_window_in_background(app_id);
+        self.sway.settle();
+        id
+    }
+
+    pub fn close_window(&self, id: i64) {
+        self.sway.close_window(id);
+        self.sway.settle();
+    }
+
+    pub fn set_urgent(&self, id: i64, urgent: bool) {
+        self.sway.set_urgent(id, urgent);
+        self.sway.settle();
+    }
+
+    pub fn command(&self, cmd: &str) {
+        self.sway.command(cmd);
+        self.sway.settle();
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+fn wait_for(path: &Path) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !path.exists() {
+        assert!(
+            Instant::now() < deadline,
+            "persway never created its socket"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+}
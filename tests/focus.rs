@@ -0,0 +1,159 @@
+mod common;
+
+use common::Session;
+use std::io::{BufRead, BufReader};
+use std::process::Stdio;
+
+#[test]
+fn focus_prev_walks_the_focus_history_across_workspaces() {
+    let session = Session::start(&[]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    session.command("floating enable");
+    session.command("workspace number 2");
+    let c = session.new_window("c");
+    session.command(&format!("[con_id={}] focus", a));
+    session.persway(&["focus-prev"]);
+    assert_eq!(session.sway.focused(), c);
+    session.persway(&["focus-prev"]);
+    assert_eq!(session.sway.focused(), b);
+    session.persway(&["focus-next"]);
+    assert_eq!(session.sway.focused(), c);
+    session.command(&format!("[con_id={}] focus", b));
+    session.persway(&["focus-prev"]);
+    assert_eq!(session.sway.focused(), c);
+}
+
+#[test]
+fn windows_walked_past_are_dimmed() {
+    let session = Session::start(&["--dim-inactive", "0.8", "--dim-fade", "0"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window("c");
+    session.command(&format!("[con_id={}] focus", a));
+    session.persway(&["focus-prev"]);
+    assert_eq!(session.sway.focused(), c);
+    session.persway(&["focus-prev"]);
+    assert_eq!(session.sway.focused(), b);
+    let last_opacity = |id: i64| {
+        session
+            .sway
+            .commands()
+            .into_iter()
+            .rfind(|cmd| cmd.starts_with(&format!("[con_id={id}] opacity")))
+    };
+    assert_eq!(last_opacity(c), Some(format!("[con_id={c}] opacity 0.80")));
+    assert_eq!(last_opacity(b), Some(format!("[con_id={b}] opacity 1.00")));
+}
+
+#[test]
+fn list_windows_prints_a_line_to_focus_each_window_by() {
+    let session = Session::start(&["-d", "stack_main"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window("c");
+    let d = session.new_window("d");
+    session.command("floating enable");
+    session.command(&format!("[con_id={}] focus", a));
+    assert_eq!(
+        session.persway(&["list-windows"]),
+        format!(
+            "{a} 1: a - a [stack]\n{b} 1: b - b [stack]\n{c} 1: c - c [main]\n{d} 1: d - d [leaf]\n"
+        )
+    );
+    let mru = session.persway(&["list-windows", "--mru"]);
+    let lines: Vec<&str> = mru.lines().collect();
+    assert_eq!(lines[0], format!("{a} 1: a - a [stack]"));
+    assert_eq!(lines[1], format!("{c} 1: c - c [main]"));
+    assert_eq!(lines[2], format!("{d} 1: d - d [leaf]"));
+    session.persway(&["focus-window", lines[2]]);
+    assert_eq!(session.sway.focused(), d);
+    session.persway(&["focus-window", lines[3]]);
+    assert_eq!(session.sway.focused(), b);
+}
+
+#[test]
+fn focus_hooks_run_on_matching_windows() {
+    let session = Session::start(&[
+        "--on-focus",
+        "[app_id=\"^mpv$\" tiling] opacity 1; inhibit_idle focus",
+        "--on-focus",
+        "[app_id=\"foot\"] opacity 0.9",
+        "--on-focus-leave",
+        "[app_id=\"^mpv$\"] inhibit_idle none",
+    ]);
+    let foot = session.new_window("foot");
+    let mpv = session.new_window("mpv");
+    session.command(&format!("[con_id={}] focus", foot));
+    let commands = session.sway.commands();
+    assert!(commands.contains(&format!(
+        "[con_id={mpv}] opacity 1; [con_id={mpv}] inhibit_idle focus"
+    )));
+    assert!(commands.contains(&format!("[con_id={mpv}] inhibit_idle none")));
+    assert!(commands.contains(&format!("[con_id={foot}] opacity 0.9")));
+    assert!(!commands.contains(&format!("[con_id={foot}] opacity 1")));
+}
+
+#[test]
+fn focus_urgent_jumps_to_the_window_urgent_the_longest() {
+    let session = Session::start(&[]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window("c");
+    session.set_urgent(b, true);
+    session.set_urgent(a, true);
+    assert_eq!(
+        session.persway(&["status"]),
+        format!("{{\"urgent\":2,\"urgent_windows\":[{b},{a}]}}\n")
+    );
+    assert_eq!(session.sway.focused(), c);
+    session.persway(&["focus-urgent"]);
+    assert_eq!(session.sway.focused(), b);
+    session.persway(&["focus-urgent"]);
+    assert_eq!(session.sway.focused(), a);
+    assert_eq!(
+        session.persway(&["status"]),
+        "{\"urgent\":0,\"urgent_windows\":[]}\n"
+    );
+}
+
+#[test]
+fn urgent_windows_of_chosen_apps_are_focused_right_away() {
+    let session = Session::start(&["--auto-focus-urgent", "[app_id=\"chat\"]"]);
+    let chat = session.new_window("chat");
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    session.set_urgent(a, true);
+    assert_eq!(session.sway.focused(), b);
+    session.set_urgent(chat, true);
+    assert_eq!(session.sway.focused(), chat);
+}
+
+#[test]
+fn subscribe_prints_the_status_on_every_change() {
+    let session = Session::start(&[]);
+    let a = session.new_window("a");
+    session.new_window("b");
+    let mut subscriber = session
+        .client(&["subscribe"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(subscriber.stdout.take().unwrap()).lines();
+    assert_eq!(
+        lines.next().unwrap().unwrap(),
+        "{\"urgent\":0,\"urgent_windows\":[]}"
+    );
+    session.set_urgent(a, true);
+    assert_eq!(
+        lines.next().unwrap().unwrap(),
+        format!("{{\"urgent\":1,\"urgent_windows\":[{a}]}}")
+    );
+    session.command(&format!("[con_id={a}] focus"));
+    assert_eq!(
+        lines.next().unwrap().unwrap(),
+        "{\"urgent\":0,\"urgent_windows\":[]}"
+    );
+    subscriber.kill().unwrap();
+    subscriber.wait().unwrap();
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::{fake_sway::FakeSway, socket_path, Session};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn wait_for_lines(path: &std::path::Path, count: usize) -> Vec<String> {
+    let start = Instant::now();
+    loop {
+        let lines: Vec<String> = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect();
+        if lines.len() >= count || start.elapsed() > Duration::from_secs(5) {
+            return lines;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn exec_hooks_get_the_window_in_env_and_stdin() {
+    let out = socket_path("exec");
+    let program = format!(
+        "echo \"$PERSWAY_EVENT $PERSWAY_WINDOW_ID $PERSWAY_APP_ID $PERSWAY_APP_NAME $PERSWAY_WORKSPACE $PERSWAY_LAYOUT\" >> {out}; cat >> {out}; echo >> {out}",
+        out = out.display()
+    );
+    let session = Session::start(&["-d", "stack_main", "--exec-on-new", &program]);
+    let a = session.new_window("Foot");
+    let lines = wait_for_lines(&out, 2);
+    let _ = fs::remove_file(&out);
+    assert_eq!(lines[0], format!("new {a} Foot foot 1 stack_main"));
+    let json: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(json["id"], a);
+    assert_eq!(json["app_id"], "Foot");
+    assert_eq!(json["app_name"], "foot");
+    assert_eq!(json["workspace"], "1");
+}
+
+#[test]
+fn dimming_fades_only_windows_changing_focus() {
+    let session = Session::start(&[
+        "--dim-inactive",
+        "0.8",
+        "--dim-fade",
+        "0",
+        "--dim-exclude",
+        "[app_id=\"mpv\"]",
+    ]);
+    let a = session.new_window("a");
+    let mpv = session.new_window("mpv");
+    let b = session.new_window("b");
+    session.command(&format!("[con_id={}] focus", a));
+    let mut opacity: Vec<String> = session
+        .sway
+        .commands()
+        .into_iter()
+        .filter(|cmd| cmd.contains("opacity"))
+        .collect();
+    // the last two fade at the same time
+    opacity[1..].sort();
+    assert_eq!(
+        opacity,
+        vec![
+            format!("[con_id={a}] opacity 0.80"),
+            format!("[con_id={a}] opacity 1.00"),
+            format!("[con_id={b}] opacity 0.80"),
+        ]
+    );
+    assert!(!opacity.iter().any(|cmd| cmd.contains(&mpv.to_string())));
+}
+
+#[test]
+fn windows_open_before_the_daemon_are_dimmed_floating_or_not() {
+    let sway = FakeSway::start(&socket_path("sway"));
+    let a = sway.new_window("a");
+    let b = sway.new_window("b");
+    sway.command("floating enable");
+    let c = sway.new_window("c");
+    let session = Session::start_on(sway, &["--dim-inactive", "0.8", "--dim-fade", "0"]);
+    session.command(&format!("[con_id={}] focus", a));
+    let commands = session.sway.commands();
+    assert!(commands
+        .iter()
+        .any(|cmd| cmd.contains(&format!("[con_id={b}] opacity 0.80"))));
+    assert!(commands
+        .iter()
+        .any(|cmd| cmd.contains(&format!("[con_id={c}] opacity 0.80"))));
+}
+
+#[test]
+fn fades_cut_short_leave_windows_at_their_last_opacity() {
+    let session = Session::start(&["--dim-inactive", "0.5", "--dim-fade", "200"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    for id in [a, b, a, b, a] {
+        session.sway.command(&format!("[con_id={}] focus", id));
+        thread::sleep(Duration::from_millis(30));
+    }
+    session.sway.settle();
+    let last_opacity = |id: i64| {
+        session
+            .sway
+            .commands()
+            .into_iter()
+            .rfind(|cmd| cmd.starts_with(&format!("[con_id={id}] opacity")))
+    };
+    assert_eq!(last_opacity(a), Some(format!("[con_id={a}] opacity 1.00")));
+    assert_eq!(last_opacity(b), Some(format!("[con_id={b}] opacity 0.50")));
+}
+
+#[test]
+fn windows_opened_without_focus_are_dimmed() {
+    let session = Session::start(&["--dim-inactive", "0.8", "--dim-fade", "0"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window_in_background("c");
+    assert_eq!(session.sway.focused(), b);
+    let opacity: Vec<String> = session
+        .sway
+        .commands()
+        .into_iter()
+        .filter(|cmd| cmd.contains("opacity"))
+        .collect();
+    assert_eq!(
+        opacity,
+        vec![
+            format!("[con_id={a}] opacity 0.80"),
+            format!("[con_id={c}] opacity 0.80"),
+        ]
+    );
+}
+
+#[test]
+fn window_hooks_run_on_their_events() {
+    let session = Session::start(&[
+        "--on-new",
+        "[app_id=\"pavucontrol\"] floating enable",
+        "--on-floating",
+        "[floating] border normal",
+    ]);
+    let foot = session.new_window("foot");
+    let pavucontrol = session.new_window("pavucontrol");
+    let commands = session.sway.commands();
+    assert!(commands.contains(&format!("[con_id={pavucontrol}] floating enable")));
+    assert!(commands.contains(&format!("[con_id={pavucontrol}] border normal")));
+    assert!(!commands
+        .iter()
+        .any(|cmd| cmd.contains(&format!("[con_id={foot}]"))));
+}
+
+#[test]
+fn semicolons_within_quotes_dont_split_hook_commands() {
+    let session = Session::start(&["--on-new", "mark \"a;b\"; border pixel 2"]);
+    let foot = session.new_window("foot");
+    assert!(session.sway.commands().contains(&format!(
+        "[con_id={foot}] mark \"a;b\"; [con_id={foot}] border pixel 2"
+    )));
+}
+
+#[test]
+fn close_hooks_match_the_closed_window_on_its_own() {
+    let session = Session::start(&[
+        "--on-close",
+        "[app_id=\"mpv\"] nop mpv closed",
+        "--on-close",
+        "[workspace=\"1\"] nop window closed on 1",
+    ]);
+    session.new_window("foot");
+    let mpv = session.new_window("mpv");
+    session.close_window(mpv);
+    let commands = session.sway.commands();
+    assert!(commands.contains(&String::from("nop mpv closed")));
+    assert!(!commands.iter().any(|cmd| cmd.contains("on 1")));
+}
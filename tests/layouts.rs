@@ -0,0 +1,392 @@
+mod common;
+
+use common::Session;
+
+#[test]
+fn spiral_splits_new_windows_alternately() {
+    let session = Session::start(&["-d", "spiral"]);
+    session.new_window("a");
+    session.new_window("b");
+    session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[a V[b H[c]]]");
+}
+
+#[test]
+fn spiral_keeps_layout_when_closing() {
+    let session = Session::start(&["-d", "spiral"]);
+    session.new_window("a");
+    session.new_window("b");
+    let c = session.new_window("c");
+    session.close_window(c);
+    assert_eq!(session.sway.workspace(1), "H[a V[b]]");
+}
+
+#[test]
+fn stack_main_stacks_new_windows() {
+    let session = Session::start(&["-d", "stack_main"]);
+    session.new_window("a");
+    session.new_window("b");
+    assert_eq!(session.sway.workspace(1), "H[S[a] b]");
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[S[a b] c]");
+    assert_eq!(session.sway.focused(), c);
+    assert_eq!(session.sway.width_percent(c), 70);
+}
+
+#[test]
+fn stack_main_respects_size_and_stack_layout() {
+    let session = Session::start(&["-d", "stack_main", "-s", "60", "-k", "tabbed"]);
+    session.new_window("a");
+    let b = session.new_window("b");
+    session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[T[a b] c]");
+    assert_eq!(session.sway.width_percent(b), 40);
+}
+
+#[test]
+fn stack_main_promotes_from_stack_when_main_closes() {
+    let session = Session::start(&["-d", "stack_main"]);
+    session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window("c");
+    session.close_window(c);
+    assert_eq!(session.sway.workspace(1), "H[S[a] b]");
+    assert_eq!(session.sway.width_percent(b), 70);
+}
+
+#[test]
+fn stack_main_relayouts_when_main_moves_to_other_workspace() {
+    let session = Session::start(&["-d", "stack_main"]);
+    session.new_window("a");
+    session.new_window("b");
+    let c = session.new_window("c");
+    session.command(&format!("[con_id={}] move to workspace number 2", c));
+    assert_eq!(session.sway.workspace(1), "H[S[a] b]");
+    assert_eq!(session.sway.workspace(2), "H[c]");
+}
+
+#[test]
+fn stack_main_handles_floating_toggle() {
+    let session = Session::start(&["-d", "stack_main"]);
+    session.new_window("a");
+    session.new_window("b");
+    let c = session.new_window("c");
+    session.command(&format!("[con_id={}] floating enable", c));
+    assert_eq!(session.sway.workspace(1), "H[S[a] b]");
+    session.command(&format!("[con_id={}] floating disable", c));
+    assert_eq!(session.sway.workspace(1), "H[S[a b] c]");
+}
+
+#[test]
+fn change_layout_relayouts_existing_windows() {
+    let session = Session::start(&[]);
+    session.new_window("a");
+    session.new_window("b");
+    session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[a b c]");
+    session.persway(&["change-layout", "stack-main"]);
+    assert_eq!(session.sway.workspace(1), "H[S[a b] c]");
+}
+
+#[test]
+fn cycle_and_toggle_layout_keep_layout_options() {
+    let session = Session::start(&[]);
+    session.new_window("a");
+    session.new_window("b");
+    let c = session.new_window("c");
+    session.persway(&["change-layout", "stack-main", "-s", "60"]);
+    assert_eq!(session.sway.workspace(1), "H[S[a b] c]");
+    session.persway(&["cycle-layout", "--layouts", "stack_main,columns"]);
+    assert_eq!(session.sway.workspace(1), "H[a b c]");
+    session.persway(&["toggle-layout", "columns", "stack_main"]);
+    assert_eq!(session.sway.workspace(1), "H[S[a b] c]");
+    assert_eq!(session.sway.width_percent(c), 60);
+}
+
+#[test]
+fn change_layout_restores_options_not_given() {
+    let session = Session::start(&[]);
+    session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window("c");
+    session.persway(&["change-layout", "stack-main", "-s", "60", "-l", "tabbed"]);
+    session.persway(&["change-layout", "spiral"]);
+    session.persway(&["change-layout", "stack-main"]);
+    assert_eq!(session.sway.workspace(1), "H[T[a b] c]");
+    assert_eq!(session.sway.width_percent(c), 60);
+    session.persway(&["change-layout", "stack-main", "-s", "80"]);
+    session.persway(&["change-layout", "stack-main", "-l", "tabbed"]);
+    assert_eq!(session.sway.workspace(1), "H[T[a b] c]");
+    assert_eq!(session.sway.width_percent(b), 20);
+}
+
+#[test]
+fn undo_and_redo_restore_the_arrangement() {
+    let session = Session::start(&["-d", "stack_main"]);
+    session.new_window("a");
+    session.new_window("b");
+    session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[S[a b] c]");
+    session.persway(&["stack-main-rotate-next"]);
+    assert_eq!(session.sway.workspace(1), "H[S[b c] a]");
+    session.persway(&["change-layout", "columns"]);
+    session.persway(&["undo"]);
+    assert_eq!(session.sway.workspace(1), "H[S[b c] a]");
+    session.persway(&["undo"]);
+    assert_eq!(session.sway.workspace(1), "H[S[a b] c]");
+    // commands that change nothing keep what redo goes forward to
+    session.persway(&["rotate"]);
+    session.persway(&["change-layout", "stack-main"]);
+    session.persway(&["redo"]);
+    assert_eq!(session.sway.workspace(1), "H[S[b c] a]");
+}
+
+#[test]
+fn toggle_zoom_restores_stack_main() {
+    let session = Session::start(&["-d", "stack_main", "-s", "60"]);
+    session.new_window("a");
+    session.new_window("b");
+    let c = session.new_window("c");
+    session.persway(&["toggle-zoom"]);
+    assert_eq!(session.sway.workspace(1), "T[a b c]");
+    assert_eq!(session.sway.focused(), c);
+    let d = session.new_window("d");
+    assert_eq!(session.sway.workspace(1), "T[a b c d]");
+    session.persway(&["toggle-zoom"]);
+    assert_eq!(session.sway.workspace(1), "H[S[a b c] d]");
+    assert_eq!(session.sway.focused(), d);
+    assert_eq!(session.sway.width_percent(d), 60);
+}
+
+#[test]
+fn monocle_flattens_windows_nested_in_splits() {
+    let session = Session::start(&["-d", "monocle"]);
+    session.new_window("a");
+    session.new_window("b");
+    assert_eq!(session.sway.workspace(1), "T[a b]");
+    session.command("split v");
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "T[a b c]");
+    assert_eq!(session.sway.focused(), c);
+}
+
+#[test]
+fn dwindle_gives_the_split_window_the_larger_share() {
+    let session = Session::start(&["-d", "dwindle"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[a V[b H[c]]]");
+    assert_eq!(session.sway.width_percent(a), 62);
+    assert_eq!(session.sway.height_percent(b), 62);
+    assert_eq!(session.sway.height_percent(c), 38);
+}
+
+#[test]
+fn dwindle_can_split_in_a_fixed_direction() {
+    let session = Session::start(&[]);
+    session.persway(&["change-layout", "dwindle", "--direction", "horizontal"]);
+    session.new_window("a");
+    session.new_window("b");
+    session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[a H[b H[c]]]");
+}
+
+#[test]
+fn spiral_ratio_resizes_new_splits() {
+    let session = Session::start(&[]);
+    session.persway(&["change-layout", "spiral", "--ratio", "70"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    assert_eq!(session.sway.workspace(1), "H[a V[b]]");
+    assert_eq!(session.sway.width_percent(a), 70);
+    assert_eq!(session.sway.width_percent(b), 30);
+}
+
+#[test]
+fn spiral_overflows_into_tabs_at_max_depth() {
+    let session = Session::start(&[]);
+    session.persway(&["change-layout", "spiral", "--max-depth", "2"]);
+    for app_id in ["a", "b", "c", "d", "e"] {
+        session.new_window(app_id);
+    }
+    assert_eq!(session.sway.workspace(1), "H[a V[b H[c T[d e]]]]");
+}
+
+#[test]
+fn columns_keep_equal_widths() {
+    let session = Session::start(&["-d", "columns"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    session.command(&format!("[con_id={}] resize set width 80", a));
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[a b c]");
+    for id in [a, b, c] {
+        assert_eq!(session.sway.width_percent(id), 33);
+    }
+    session.close_window(b);
+    assert_eq!(session.sway.width_percent(a), 50);
+    assert_eq!(session.sway.width_percent(c), 50);
+}
+
+#[test]
+fn columns_wrap_into_rows_at_max() {
+    let session = Session::start(&[]);
+    session.persway(&["change-layout", "columns", "--max", "2"]);
+    let a = session.new_window("a");
+    session.new_window("b");
+    let c = session.new_window("c");
+    session.new_window("d");
+    let e = session.new_window("e");
+    assert_eq!(session.sway.workspace(1), "V[H[a b] H[c d] e]");
+    assert_eq!(session.sway.height_percent(a), 33);
+    assert_eq!(session.sway.width_percent(a), 50);
+    assert_eq!(session.sway.height_percent(e), 33);
+    session.close_window(c);
+    assert_eq!(session.sway.workspace(1), "V[H[a b] H[d e]]");
+    assert_eq!(session.sway.height_percent(e), 50);
+}
+
+#[test]
+fn rows_keep_equal_heights() {
+    let session = Session::start(&["-d", "rows"]);
+    let a = session.new_window("a");
+    session.new_window("b");
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "V[a b c]");
+    assert_eq!(session.sway.height_percent(a), 33);
+    assert_eq!(session.sway.height_percent(c), 33);
+}
+
+#[test]
+fn scrolling_stashes_columns_that_dont_fit() {
+    let session = Session::start(&["-d", "scrolling"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    assert_eq!(session.sway.workspace(1), "H[a b]");
+    assert_eq!(session.sway.width_percent(a), 50);
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[T[a] b c]");
+    assert_eq!(session.sway.focused(), c);
+    assert_eq!(session.sway.width_percent(b), 48);
+    session.command(&format!("[con_id={}] focus", a));
+    assert_eq!(session.sway.workspace(1), "H[a b T[c]]");
+    assert_eq!(session.sway.focused(), a);
+}
+
+#[test]
+fn scrolling_commands_move_the_view() {
+    let session = Session::start(&["-d", "scrolling"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    let c = session.new_window("c");
+    session.persway(&["scroll-left"]);
+    assert_eq!(session.sway.workspace(1), "H[T[a] b c]");
+    assert_eq!(session.sway.focused(), b);
+    session.persway(&["scroll-left"]);
+    assert_eq!(session.sway.workspace(1), "H[a b T[c]]");
+    assert_eq!(session.sway.focused(), a);
+    session.persway(&["scroll-right"]);
+    session.persway(&["scroll-right"]);
+    assert_eq!(session.sway.workspace(1), "H[T[a] b c]");
+    assert_eq!(session.sway.focused(), c);
+    session.persway(&["column-width", "100"]);
+    assert_eq!(session.sway.workspace(1), "H[T[a b] c]");
+    assert_eq!(session.sway.width_percent(c), 95);
+    session.close_window(c);
+    assert_eq!(session.sway.workspace(1), "H[T[a] b]");
+    assert_eq!(session.sway.focused(), b);
+}
+
+#[test]
+fn bsp_places_the_next_window_by_preselection() {
+    let session = Session::start(&["-d", "bsp"]);
+    session.new_window("a");
+    let b = session.new_window("b");
+    assert_eq!(session.sway.workspace(1), "H[a V[b]]");
+    session.persway(&["presel", "left"]);
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[a H[V[c] b]]");
+    assert_eq!(session.sway.focused(), c);
+    session.command(&format!("[con_id={}] focus", b));
+    session.persway(&["presel", "up"]);
+    session.persway(&["presel", "cancel"]);
+    session.new_window("d");
+    assert_eq!(session.sway.workspace(1), "H[a H[V[c] V[b V[d]]]]");
+}
+
+#[test]
+fn bsp_rotates_and_flips() {
+    let session = Session::start(&["-d", "bsp"]);
+    session.new_window("a");
+    session.new_window("b");
+    session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[a V[b H[c]]]");
+    session.persway(&["flip", "horizontal"]);
+    assert_eq!(session.sway.workspace(1), "H[V[b H[c]] a]");
+    session.persway(&["flip", "vertical", "--parent"]);
+    assert_eq!(session.sway.workspace(1), "H[V[b H[c]] a]");
+    session.persway(&["rotate"]);
+    assert_eq!(session.sway.workspace(1), "V[H[V[c] b] a]");
+}
+
+#[test]
+fn stack_commands_reorder_the_stack() {
+    let session = Session::start(&["-d", "stack_main"]);
+    session.new_window("a");
+    let b = session.new_window("b");
+    session.new_window("c");
+    let d = session.new_window("d");
+    assert_eq!(session.sway.workspace(1), "H[S[a b c] d]");
+    session.persway(&["stack-focus-index", "2"]);
+    assert_eq!(session.sway.focused(), b);
+    session.persway(&["stack-move-up"]);
+    assert_eq!(session.sway.workspace(1), "H[S[b a c] d]");
+    session.persway(&["stack-move-down"]);
+    session.persway(&["stack-move-down"]);
+    assert_eq!(session.sway.workspace(1), "H[S[a c b] d]");
+    assert_eq!(session.sway.focused(), b);
+    session.persway(&["stack-focus-main"]);
+    assert_eq!(session.sway.focused(), d);
+}
+
+#[test]
+fn stack_push_leaves_main_to_the_next_window() {
+    let session = Session::start(&["-d", "stack_main"]);
+    session.new_window("a");
+    session.new_window("b");
+    session.persway(&["stack-push"]);
+    assert_eq!(session.sway.workspace(1), "H[S[b a]]");
+    let c = session.new_window("c");
+    assert_eq!(session.sway.workspace(1), "H[S[b a] c]");
+    assert_eq!(session.sway.width_percent(c), 70);
+}
+
+#[test]
+fn explain_prints_the_planned_commands_without_running_them() {
+    let session = Session::start(&["-d", "stack_main"]);
+    let a = session.new_window("a");
+    let b = session.new_window("b");
+    session.command(&format!("[con_id={a}] focus"));
+    let sent = session.sway.commands().len();
+    let explanation = session.persway(&["explain", "stack-swap-main"]);
+    assert!(explanation.starts_with("tree:\n"), "{}", explanation);
+    assert!(
+        explanation.ends_with(&format!(
+            "commands:\n[con_id={b}] focus; swap container with con_id {a}; [con_id={a}] focus\n"
+        )),
+        "{}",
+        explanation
+    );
+    assert_eq!(session.sway.commands().len(), sent);
+    assert_eq!(session.sway.workspace(1), "H[S[a] b]");
+}
+
+#[test]
+fn dry_run_sends_no_commands() {
+    let session = Session::start(&["-d", "stack_main", "--dry-run"]);
+    session.new_window("a");
+    session.new_window("b");
+    assert!(session.sway.commands().is_empty());
+    assert_eq!(session.sway.workspace(1), "H[a b]");
+}
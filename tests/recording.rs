@@ -0,0 +1,50 @@
+mod common;
+
+use common::fake_sway::FakeSway;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn replaying_a_recording_computes_the_same_layout_commands() {
+    let sway = FakeSway::start(&common::socket_path("sway"));
+    let recording = common::socket_path("recording");
+    let mut recorder = common::persway(&sway)
+        .arg("record")
+        .arg(&recording)
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    while sway.subscriber_count() == 0 {
+        thread::sleep(Duration::from_millis(20));
+    }
+    let a = sway.new_window("a");
+    sway.settle();
+    let b = sway.new_window("b");
+    sway.settle();
+    recorder.kill().unwrap();
+    recorder.wait().unwrap();
+
+    let contents = std::fs::read_to_string(&recording).unwrap();
+    assert_eq!(contents.lines().count(), 4, "new and focus for each window");
+
+    let output = common::persway(&sway)
+        .arg("replay")
+        .arg(&recording)
+        .args(["-d", "stack_main", "--dry-run"])
+        .output()
+        .unwrap();
+    let log = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", log);
+    assert!(
+        log.contains(&format!("dry-run: [con_id={}] focus; split h", a)),
+        "{}",
+        log
+    );
+    assert!(log.contains(&format!(
+        "dry-run: [con_id={}] focus; split v; layout stacking; resize set width 30; [con_id={}] focus",
+        a, b
+    )));
+    assert!(sway.commands().is_empty());
+    let _ = std::fs::remove_file(&recording);
+}
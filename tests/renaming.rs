@@ -0,0 +1,141 @@
+mod common;
+
+use common::Session;
+
+#[test]
+fn workspaces_are_renamed_after_the_template() {
+    let session = Session::start(&[
+        "-w",
+        "--rename-format",
+        "{num} {focused_icon} {focused_app} (+{count})",
+        "--app-name",
+        "Org.Wezfurlong.Wezterm=term",
+        "--app-icon",
+        "firefox=F",
+        "--fallback-icon",
+        "?",
+    ]);
+    session.new_window("firefox");
+    assert_eq!(session.sway.workspace_names(), ["1 F firefox (+0)"]);
+    session.new_window("org.wezfurlong.wezterm");
+    assert_eq!(session.sway.workspace_names(), ["1 ? term (+1)"]);
+}
+
+#[test]
+fn placeholders_within_app_names_are_left_as_they_are() {
+    let session = Session::start(&[
+        "-w",
+        "--rename-format",
+        "{num}: {focused_app} {unknown}",
+        "--app-name",
+        "foot={num}{count}",
+    ]);
+    session.new_window("foot");
+    assert_eq!(
+        session.sway.workspace_names(),
+        ["1: {num}{count} {unknown}"]
+    );
+}
+
+#[test]
+fn workspace_names_are_cut_short_at_the_max_length() {
+    let session = Session::start(&[
+        "-w",
+        "--rename-format",
+        "{num}: {focused_app}, {focused_app}",
+        "--rename-max-length",
+        "12",
+    ]);
+    session.new_window("pavucontrol");
+    assert_eq!(session.sway.workspace_names(), ["1: pavucont…"]);
+}
+
+#[test]
+fn workspaces_can_be_named_after_all_their_apps() {
+    let session = Session::start(&[
+        "-w",
+        "--rename-format",
+        "{num}: {apps}",
+        "--rename-apps",
+        "tree",
+    ]);
+    session.new_window("foot");
+    session.new_window("firefox");
+    let floating = session.new_window("foot");
+    session.command("floating enable");
+    assert_eq!(session.sway.workspace_names(), ["1: foot×2, firefox"]);
+    session.close_window(floating);
+    assert_eq!(session.sway.workspace_names(), ["1: foot, firefox"]);
+}
+
+#[test]
+fn apps_can_be_ordered_by_when_they_were_focused() {
+    let session = Session::start(&[
+        "-w",
+        "--rename-format",
+        "{num}: {apps}",
+        "--rename-apps",
+        "mru",
+    ]);
+    let foot = session.new_window("foot");
+    session.new_window("firefox");
+    session.new_window("mpv");
+    assert_eq!(session.sway.workspace_names(), ["1: mpv, firefox, foot"]);
+    session.command(&format!("[con_id={foot}] focus"));
+    assert_eq!(session.sway.workspace_names(), ["1: foot, mpv, firefox"]);
+}
+
+#[test]
+fn workspaces_that_arent_focused_are_renamed_too() {
+    let session = Session::start(&[
+        "-w",
+        "--rename-format",
+        "{num}: {apps}",
+        "--rename-apps",
+        "tree",
+    ]);
+    session.new_window("foot");
+    session.new_window("firefox");
+    session.command("move container to workspace number 2");
+    assert_eq!(session.sway.workspace_names(), ["1: foot", "2: firefox"]);
+    session.command("workspace number 2");
+    let mpv = session.new_window("mpv");
+    session.command("workspace number 1");
+    assert_eq!(
+        session.sway.workspace_names(),
+        ["1: foot", "2: firefox, mpv"]
+    );
+    session.close_window(mpv);
+    assert_eq!(session.sway.workspace_names(), ["1: foot", "2: firefox"]);
+}
+
+#[test]
+fn workspaces_without_a_number_keep_their_name_in_front() {
+    let session = Session::start(&["-w", "--rename-format", "{num} {focused_app}"]);
+    session.command("workspace web");
+    session.new_window("foot");
+    assert_eq!(session.sway.workspace_names(), ["web foot"]);
+    session.new_window("firefox");
+    assert_eq!(session.sway.workspace_names(), ["web firefox"]);
+}
+
+#[test]
+fn names_with_commas_survive_a_change_of_layout() {
+    let session = Session::start(&[
+        "-w",
+        "--rename-format",
+        "{num}: {apps}",
+        "--rename-apps",
+        "tree",
+    ]);
+    session.new_window("foot");
+    session.new_window("firefox");
+    assert_eq!(session.sway.workspace_names(), ["1: foot, firefox"]);
+    session.persway(&["change-layout", "stack-main"]);
+    assert_eq!(session.sway.workspace_names(), ["1: foot, firefox"]);
+    assert!(!session
+        .sway
+        .commands()
+        .iter()
+        .any(|cmd| cmd.contains("rename workspace to")));
+}